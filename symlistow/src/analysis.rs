@@ -0,0 +1,393 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use semver::{Comparator, Op, Prerelease, Version, VersionReq};
+
+use crate::{ExecutableBin, ExecutableVerificationError};
+
+/// Groups bins by the major component of their parsed semver version.
+///
+/// # Returns
+/// A `(by_major, unparsed)` tuple:
+/// * `by_major` - the bins keyed by major version, each group in the order of `bins`
+/// * `unparsed` - the bins whose version report doesn't parse, in the order of `bins`
+pub fn group_by_major(
+    bins: &[ExecutableBin],
+) -> (BTreeMap<u64, Vec<&ExecutableBin>>, Vec<&ExecutableBin>) {
+    let mut groups: BTreeMap<u64, Vec<&ExecutableBin>> = BTreeMap::new();
+    let mut unparsed = Vec::new();
+    for bin in bins {
+        match bin.version() {
+            Ok(version) => groups.entry(version.major).or_default().push(bin),
+            Err(_) => unparsed.push(bin),
+        }
+    }
+    (groups, unparsed)
+}
+
+/// A tool whose installed version is behind the latest known release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outdated {
+    pub name: String,
+    pub installed: Version,
+    pub latest: Version,
+}
+
+/// Compares each bin with the latest version `latest` lists for its name.
+///
+/// Bins whose name isn't in `latest`, or whose version report doesn't parse, are
+/// skipped. The result keeps the order of `bins`.
+pub fn find_outdated(bins: &[ExecutableBin], latest: &BTreeMap<String, Version>) -> Vec<Outdated> {
+    bins.iter()
+        .filter_map(|bin| {
+            let newest = latest.get(bin.name().as_ref())?;
+            let installed = bin.version().ok()?;
+            (installed < *newest).then(|| Outdated {
+                name: bin.name().into_owned(),
+                installed,
+                latest: newest.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Groups bins that are the same file, i.e. hard links sharing a device and inode.
+///
+/// Links are followed. Groups are ordered by their first member in `bins`, and a
+/// bin whose metadata can't be read is a group of its own.
+pub fn group_by_inode(bins: &[ExecutableBin]) -> Vec<Vec<&ExecutableBin>> {
+    let mut groups: Vec<Vec<&ExecutableBin>> = Vec::new();
+    let mut index_of = HashMap::new();
+    for bin in bins {
+        let Ok(metadata) = fs::metadata(bin.path()) else {
+            groups.push(vec![bin]);
+            continue;
+        };
+        let index = *index_of
+            .entry((metadata.dev(), metadata.ino()))
+            .or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+        groups[index].push(bin);
+    }
+    groups
+}
+
+/// The disk space taken by `bins`, in bytes.
+///
+/// Links are followed, and files that share a device and inode, such as hard
+/// links or several links to one binary, count once. Bins whose metadata can't
+/// be read count as zero.
+pub fn total_size(bins: &[ExecutableBin]) -> u64 {
+    let mut seen = HashSet::new();
+    bins.iter()
+        .filter_map(|bin| fs::metadata(bin.path()).ok())
+        .filter(|metadata| seen.insert((metadata.dev(), metadata.ino())))
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// A tool that got worse compared to a baseline install.
+#[derive(Debug)]
+pub enum Regression {
+    /// The tool now reports a lower version than in the baseline.
+    Downgraded {
+        name: String,
+        baseline: Version,
+        current: Version,
+    },
+    /// The tool is absent or no longer verifies.
+    Missing {
+        name: String,
+        error: ExecutableVerificationError,
+    },
+    /// The baseline version parsed but the tool's report no longer does.
+    Unparseable {
+        name: String,
+        baseline: Version,
+        report: String,
+    },
+}
+
+/// Checks that every tool of `baseline` is in `current_dir` at the same or a
+/// higher version.
+///
+/// Each tool is looked up by its basename and verified. Versions are compared as
+/// semver; a tool whose baseline report doesn't parse can only regress by going
+/// missing, and one whose report stops parsing has regressed.
+///
+/// # Returns
+/// * Every regression, in the order of `baseline`, if there are any
+pub fn assert_no_regression(
+    baseline: &[ExecutableBin],
+    current_dir: &Path,
+) -> Result<(), Vec<Regression>> {
+    let regressions = baseline
+        .iter()
+        .filter_map(|before| {
+            let name = before.name().into_owned();
+            let now = match ExecutableBin::new(current_dir.join(&name)) {
+                Ok(now) => now,
+                Err(error) => return Some(Regression::Missing { name, error }),
+            };
+            let baseline = before.version().ok()?;
+            let Ok(current) = now.version() else {
+                return Some(Regression::Unparseable {
+                    name,
+                    baseline,
+                    report: now.version_report().to_string(),
+                });
+            };
+            (current < baseline).then_some(Regression::Downgraded {
+                name,
+                baseline,
+                current,
+            })
+        })
+        .collect::<Vec<_>>();
+    if regressions.is_empty() {
+        Ok(())
+    } else {
+        Err(regressions)
+    }
+}
+
+/// A requirement on one tool's version that depends on another tool's version.
+#[derive(Debug, Clone)]
+pub struct CompatRule {
+    /// The name of the tool the requirement applies to.
+    pub tool: String,
+    /// The name of the tool whose version the requirement is derived from.
+    pub depends_on: String,
+    /// Derives the requirement from the version of `depends_on`.
+    pub requirement: fn(&Version) -> VersionReq,
+}
+
+impl CompatRule {
+    pub fn new(
+        tool: impl Into<String>,
+        depends_on: impl Into<String>,
+        requirement: fn(&Version) -> VersionReq,
+    ) -> Self {
+        Self {
+            tool: tool.into(),
+            depends_on: depends_on.into(),
+            requirement,
+        }
+    }
+
+    /// A rule that `tool` has the same major and minor version as `depends_on`,
+    /// as a compiler and its matching linker usually must.
+    pub fn same_minor(tool: impl Into<String>, depends_on: impl Into<String>) -> Self {
+        Self::new(tool, depends_on, |version| VersionReq {
+            comparators: vec![Comparator {
+                op: Op::Tilde,
+                major: version.major,
+                minor: Some(version.minor),
+                patch: None,
+                pre: Prerelease::EMPTY,
+            }],
+        })
+    }
+}
+
+/// A pair of tools whose versions break a [`CompatRule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatViolation {
+    pub tool: String,
+    pub version: Version,
+    pub depends_on: String,
+    pub depends_on_version: Version,
+    /// The requirement `version` fails, as derived from `depends_on_version`.
+    pub requirement: VersionReq,
+}
+
+/// Checks the versions of `bins` against each of `rules`.
+///
+/// Tools are looked up by name, taking the first bin of that name. A rule is
+/// skipped if either of its tools is missing from `bins` or has a version report
+/// that doesn't parse. The result keeps the order of `rules`.
+pub fn check_compatibility(bins: &[ExecutableBin], rules: &[CompatRule]) -> Vec<CompatViolation> {
+    let version_of = |name: &str| {
+        bins.iter()
+            .find(|bin| bin.name() == name)
+            .and_then(|bin| bin.version().ok())
+    };
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let version = version_of(&rule.tool)?;
+            let depends_on_version = version_of(&rule.depends_on)?;
+            let requirement = (rule.requirement)(&depends_on_version);
+            (!requirement.matches(&version)).then(|| CompatViolation {
+                tool: rule.tool.clone(),
+                version,
+                depends_on: rule.depends_on.clone(),
+                depends_on_version,
+                requirement,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    fn bin(path: &str, report: &str) -> ExecutableBin {
+        ExecutableBin::from_parts(path.into(), report.into(), None)
+    }
+
+    #[test]
+    fn group_by_major_separates_majors_and_unparsed_reports() {
+        let bins = [
+            bin("/opt/v1/bin/tool", "tool 1.4.0"),
+            bin("/opt/v2/bin/tool", "tool 2.0.1"),
+            bin("/usr/bin/tool", "tool 1.9.2"),
+            bin("/opt/dev/bin/tool", "tool (built from source)"),
+        ];
+        let (groups, unparsed) = group_by_major(&bins);
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(groups[&1], [&bins[0], &bins[2]]);
+        assert_eq!(groups[&2], [&bins[1]]);
+        assert_eq!(unparsed, [&bins[3]]);
+    }
+
+    #[test]
+    fn find_outdated_reports_tools_behind_the_feed() {
+        let bins = [
+            bin("/usr/bin/git", "git version 2.40.1"),
+            bin("/usr/bin/jq", "jq-1.7.1"),
+            bin("/usr/bin/rg", "ripgrep 14.1.0"),
+            bin("/usr/bin/make", "GNU Make 4.3"),
+        ];
+        let latest = [("git", "2.43.0"), ("jq", "1.7.1"), ("make", "4.4.1")]
+            .into_iter()
+            .map(|(name, version)| (name.to_string(), Version::parse(version).unwrap()))
+            .collect();
+
+        let outdated = find_outdated(&bins, &latest);
+        assert_eq!(
+            outdated,
+            [Outdated {
+                name: "git".into(),
+                installed: Version::new(2, 40, 1),
+                latest: Version::new(2, 43, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn group_by_inode_puts_hard_links_together() {
+        let dir = TempDir::new();
+        fs::write(dir.join("gcc"), "same file").unwrap();
+        fs::hard_link(dir.join("gcc"), dir.join("cc")).unwrap();
+        fs::write(dir.join("clang"), "same file").unwrap();
+        let at = |name: &str| ExecutableBin::new_unverified(dir.join(name), String::new());
+        let bins = [at("gcc"), at("clang"), at("cc"), at("missing")];
+
+        let groups = group_by_inode(&bins);
+        assert_eq!(
+            groups,
+            [vec![&bins[0], &bins[2]], vec![&bins[1]], vec![&bins[3]]]
+        );
+    }
+
+    #[test]
+    fn assert_no_regression_reports_downgraded_missing_and_unparseable_tools() {
+        let dir = TempDir::new();
+        write_script(&dir.join("git"), "echo 'git version 2.40.0'");
+        write_script(&dir.join("jq"), "echo 'jq-1.7.1'");
+        write_script(&dir.join("rg"), "echo 'ripgrep (dev build)'");
+        write_script(&dir.join("dev"), "echo 'dev 0.1.0'");
+        let baseline = [
+            bin("/usr/bin/git", "git version 2.43.0"),
+            bin("/usr/bin/jq", "jq-1.6.0"),
+            bin("/usr/bin/make", "GNU Make 4.3.0"),
+            bin("/usr/bin/rg", "ripgrep 14.1.0"),
+            bin("/usr/bin/dev", "dev (unstamped)"),
+        ];
+
+        let regressions = assert_no_regression(&baseline, dir.path()).unwrap_err();
+        match regressions.as_slice() {
+            [
+                Regression::Downgraded {
+                    name: git,
+                    baseline: before,
+                    current,
+                },
+                Regression::Missing { name: make, .. },
+                Regression::Unparseable {
+                    name: rg,
+                    baseline: rg_before,
+                    report,
+                },
+            ] => {
+                assert_eq!(git, "git");
+                assert_eq!(
+                    (before, current),
+                    (&Version::new(2, 43, 0), &Version::new(2, 40, 0))
+                );
+                assert_eq!(make, "make");
+                assert_eq!(rg, "rg");
+                assert_eq!(rg_before, &Version::new(14, 1, 0));
+                assert_eq!(report, "ripgrep (dev build)");
+            }
+            other => panic!("unexpected regressions: {other:?}"),
+        }
+
+        assert!(assert_no_regression(&baseline[1..2], dir.path()).is_ok());
+    }
+
+    #[test]
+    fn check_compatibility_reports_only_violating_pairs() {
+        let bins = [
+            bin("/usr/bin/clang", "clang version 17.0.6"),
+            bin("/usr/bin/lld", "LLD 17.0.2"),
+            bin("/usr/bin/gcc", "gcc 13.2.0"),
+            bin("/usr/bin/ld", "GNU ld 2.41.0"),
+            bin("/opt/bin/gcc", "gcc 9.1.0"),
+        ];
+        // A minimum ld for each gcc major
+        let binutils_for = |gcc: &Version| {
+            let minimum = if gcc.major >= 13 { ">=2.40" } else { ">=2.30" };
+            VersionReq::parse(minimum).unwrap()
+        };
+        let rules = [
+            CompatRule::same_minor("lld", "clang"),
+            CompatRule::new("ld", "gcc", binutils_for),
+            CompatRule::same_minor("gcc", "ld"),
+            CompatRule::same_minor("lld", "missing"),
+        ];
+
+        assert_eq!(
+            check_compatibility(&bins, &rules),
+            [CompatViolation {
+                tool: "gcc".into(),
+                version: Version::new(13, 2, 0),
+                depends_on: "ld".into(),
+                depends_on_version: Version::new(2, 41, 0),
+                requirement: VersionReq::parse("~2.41").unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn total_size_counts_hard_links_once() {
+        let dir = TempDir::new();
+        fs::write(dir.join("gcc"), [0; 1000]).unwrap();
+        fs::hard_link(dir.join("gcc"), dir.join("cc")).unwrap();
+        std::os::unix::fs::symlink(dir.join("gcc"), dir.join("c99")).unwrap();
+        fs::write(dir.join("clang"), [0; 300]).unwrap();
+        let at = |name: &str| ExecutableBin::new_unverified(dir.join(name), String::new());
+
+        let bins = [at("gcc"), at("cc"), at("c99"), at("clang"), at("missing")];
+        assert_eq!(total_size(&bins), 1300);
+        assert_eq!(total_size(&bins[1..2]), 1000);
+        assert_eq!(total_size(&[]), 0);
+    }
+}
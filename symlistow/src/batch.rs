@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::parallel::map_bounded;
+use crate::{ExecutableBin, ExecutableVerificationError, VerifyOptions, verify_binary};
+
+/// Err Type for a glob that couldn't be expanded
+#[derive(Debug, thiserror::Error)]
+pub enum GlobError {
+    #[error("Invalid glob pattern: {0}")]
+    Pattern(#[from] glob::PatternError),
+    #[error("Failed to expand glob: {0}")]
+    Walk(#[from] glob::GlobError),
+}
+
+/// Verifies every path matching `pattern` on up to `max_threads` threads.
+///
+/// # Returns
+/// * Each matched path mapped to its version report or verification error
+pub fn verify_glob_parallel(
+    pattern: &str,
+    max_threads: usize,
+) -> Result<BTreeMap<PathBuf, Result<String, ExecutableVerificationError>>, GlobError> {
+    let paths = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+    let results = map_bounded(&paths, max_threads, |path| verify_binary(path));
+    Ok(paths.into_iter().zip(results).collect())
+}
+
+/// Verifies `paths` in order, stopping at the first failure.
+///
+/// # Returns
+/// * Every verified binary, in the order of `paths`, if all of them verify
+/// * The index into `paths` and the error of the first one that doesn't
+pub fn verify_all_strict(
+    paths: &[PathBuf],
+) -> Result<Vec<ExecutableBin>, (usize, ExecutableVerificationError)> {
+    paths
+        .iter()
+        .enumerate()
+        .map(|(index, path)| ExecutableBin::new(path).map_err(|e| (index, e)))
+        .collect()
+}
+
+/// The result for one path of [`verify_all_within`].
+#[derive(Debug)]
+pub enum BudgetedVerification {
+    Verified(ExecutableBin),
+    /// Verification failed, including by running past the remaining budget.
+    Failed(ExecutableVerificationError),
+    /// The budget ran out before verification started.
+    Skipped,
+}
+
+/// Verifies `paths` concurrently within `total_budget` of wall-clock time.
+///
+/// Once the budget is spent no new verification starts, and each one that does
+/// start is killed when the budget runs out, so the call returns shortly after
+/// `total_budget` however many paths there are.
+///
+/// # Returns
+/// * One result per path, in the order of `paths`
+pub fn verify_all_within(paths: &[PathBuf], total_budget: Duration) -> Vec<BudgetedVerification> {
+    let deadline = Instant::now() + total_budget;
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    map_bounded(paths, threads, |path| {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return BudgetedVerification::Skipped;
+        }
+        let options = VerifyOptions {
+            timeout: Some(remaining),
+            ..VerifyOptions::default()
+        };
+        match ExecutableBin::new_with(path, &options) {
+            Ok(bin) => BudgetedVerification::Verified(bin),
+            Err(e) => BudgetedVerification::Failed(e),
+        }
+    })
+}
+
+/// Verifies `paths` concurrently, choosing each one's version arguments by name.
+///
+/// A binary whose basename is a key of `flags` is run with those arguments, e.g.
+/// `"go" => ["version"]`; any other runs `--version`.
+///
+/// # Returns
+/// * One result per path, in the order of `paths`
+pub fn verify_all_with_flags(
+    paths: &[PathBuf],
+    flags: &BTreeMap<String, Vec<String>>,
+) -> Vec<Result<ExecutableBin, ExecutableVerificationError>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    map_bounded(paths, threads, |path| {
+        let name = path.file_name().map(|name| name.to_string_lossy());
+        match name.and_then(|name| flags.get(name.as_ref())) {
+            Some(version_args) => {
+                let options = VerifyOptions {
+                    version_args: version_args.clone(),
+                    ..VerifyOptions::default()
+                };
+                ExecutableBin::new_with(path, &options)
+            }
+            None => ExecutableBin::new(path),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn verify_glob_parallel_keys_every_match_by_path() {
+        let dir = TempDir::new();
+        for n in 0..6 {
+            write_script(
+                &dir.join(format!("tool{n}-bin")),
+                &format!("echo 'tool{n} 1.{n}.0'"),
+            );
+        }
+        write_script(&dir.join("broken-bin"), "exit 3");
+        fs::write(dir.join("unmatched"), "not a tool").unwrap();
+
+        let pattern = format!("{}/*-bin", dir.path().display());
+        let results = verify_glob_parallel(&pattern, 3).unwrap();
+        assert_eq!(results.len(), 7);
+        for n in 0..6 {
+            let result = &results[&dir.join(format!("tool{n}-bin"))];
+            assert_eq!(result.as_ref().unwrap(), &format!("tool{n} 1.{n}.0"));
+        }
+        match &results[&dir.join("broken-bin")] {
+            Err(ExecutableVerificationError::VersionCallFail(status)) => {
+                assert_eq!(status.code(), Some(3));
+            }
+            other => panic!("expected VersionCallFail, got {other:?}"),
+        }
+        assert!(!results.contains_key(&dir.join("unmatched")));
+
+        assert!(matches!(
+            verify_glob_parallel("[", 3),
+            Err(GlobError::Pattern(_))
+        ));
+    }
+
+    #[test]
+    fn verify_all_strict_stops_at_the_first_failure() {
+        let dir = TempDir::new();
+        let first = write_script(&dir.join("first"), "echo 'first 1.0.0'");
+        let third = write_script(&dir.join("third"), "echo 'third 3.0.0'");
+        let paths = [first.clone(), dir.join("missing"), third.clone()];
+
+        let (index, error) = verify_all_strict(&paths).unwrap_err();
+        assert_eq!(index, 1);
+        assert!(matches!(
+            error,
+            ExecutableVerificationError::MissingPath(path) if path == paths[1]
+        ));
+
+        let bins = verify_all_strict(&[first, third]).unwrap();
+        let reports = bins
+            .iter()
+            .map(ExecutableBin::version_report)
+            .collect::<Vec<_>>();
+        assert_eq!(reports, ["first 1.0.0", "third 3.0.0"]);
+    }
+
+    #[test]
+    fn verify_all_within_skips_what_the_budget_cannot_cover() {
+        let dir = TempDir::new();
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let paths = (0..threads + 2)
+            .map(|n| write_script(&dir.join(format!("slow{n}")), "sleep 1"))
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let results = verify_all_within(&paths, Duration::from_millis(300));
+        assert!(start.elapsed() < Duration::from_millis(900));
+        assert_eq!(results.len(), paths.len());
+        let skipped = results
+            .iter()
+            .filter(|result| matches!(result, BudgetedVerification::Skipped))
+            .count();
+        assert!(skipped >= 2, "{results:?}");
+        assert!(
+            results
+                .iter()
+                .all(|result| !matches!(result, BudgetedVerification::Verified(_)))
+        );
+    }
+
+    #[test]
+    fn verify_all_with_flags_uses_each_tool_s_own_arguments() {
+        let dir = TempDir::new();
+        let echo_args = r#"echo "$(basename "$0") $*""#;
+        let paths = ["go", "cargo", "java"].map(|name| write_script(&dir.join(name), echo_args));
+        let flags = BTreeMap::from([
+            ("go".to_string(), vec!["version".to_string()]),
+            (
+                "java".to_string(),
+                vec!["-version".to_string(), "-q".to_string()],
+            ),
+        ]);
+
+        let reports = verify_all_with_flags(&paths, &flags)
+            .into_iter()
+            .map(|result| result.unwrap().version_report().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reports,
+            ["go version", "cargo --version", "java -version -q"]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::{ExecutableBin, ExecutableVerificationError};
+
+/// The size and modification time of a file, used to detect that it changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// Stamps the file at `path`, following links.
+    pub(crate) fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// Remembers version reports so unchanged binaries aren't re-executed.
+///
+/// An entry is only reused while the file's size and modification time match
+/// those seen when it was verified. The cache is safe to share between threads.
+#[derive(Debug, Default)]
+pub struct VersionCache {
+    entries: Mutex<HashMap<PathBuf, (FileStamp, String)>>,
+}
+
+impl VersionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of cached version reports.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets every cached version report.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lookup(&self, path: &Path, stamp: FileStamp) -> Option<String> {
+        match self.lock().get(path) {
+            Some((cached, report)) if *cached == stamp => Some(report.clone()),
+            _ => None,
+        }
+    }
+
+    fn store(&self, path: PathBuf, stamp: FileStamp, report: String) {
+        self.lock().insert(path, (stamp, report));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, (FileStamp, String)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ExecutableBin {
+    /// Like [`ExecutableBin::new`], but only runs the binary if `cache` has no
+    /// report for it or the file changed since that report was taken.
+    pub fn new_cached(
+        candidate: impl Into<PathBuf>,
+        cache: &VersionCache,
+    ) -> Result<Self, ExecutableVerificationError> {
+        let path = candidate.into();
+        let Ok(stamp) = FileStamp::of(&path) else {
+            return Self::new(path);
+        };
+        if let Some(version_report) = cache.lookup(&path, stamp) {
+            return Ok(Self::from_parts(path, version_report, Some(stamp)));
+        }
+        let bin = Self::new(path)?;
+        cache.store(bin.path().into(), stamp, bin.version_report().into());
+        Ok(bin)
+    }
+}
+
+/// Re-verifies `path` only if it changed since `prior` was verified.
+///
+/// # Returns
+/// * `Ok(None)` when `prior` is a verification of `path` and the file's size and
+///   modification time are unchanged, so the binary wasn't run
+/// * A fresh verification otherwise
+pub fn verify_if_changed(
+    path: &Path,
+    prior: Option<&ExecutableBin>,
+) -> Result<Option<ExecutableBin>, ExecutableVerificationError> {
+    if let Some(prior) = prior {
+        let unchanged = prior.path() == path
+            && prior
+                .stamp()
+                .is_some_and(|stamp| FileStamp::of(path).is_ok_and(|now| now == stamp));
+        if unchanged {
+            return Ok(None);
+        }
+    }
+    ExecutableBin::new(path).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    /// A tool at `dir/tool` that appends a line to `dir/runs` whenever it runs.
+    fn counting_tool(dir: &TempDir) -> PathBuf {
+        let runs = dir.join("runs");
+        write_script(
+            &dir.join("tool"),
+            &format!("echo run >> '{}'\necho tool 1.0.0", runs.display()),
+        )
+    }
+
+    fn runs(dir: &TempDir) -> usize {
+        fs::read_to_string(dir.join("runs")).map_or(0, |runs| runs.lines().count())
+    }
+
+    #[test]
+    fn new_cached_runs_an_unchanged_binary_once() {
+        let dir = TempDir::new();
+        let tool = counting_tool(&dir);
+        let cache = VersionCache::new();
+        let first = ExecutableBin::new_cached(&tool, &cache).unwrap();
+        let second = ExecutableBin::new_cached(&tool, &cache).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(second.version_report(), "tool 1.0.0");
+        assert_eq!(runs(&dir), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn verify_if_changed_skips_unchanged_files_only() {
+        let dir = TempDir::new();
+        let tool = counting_tool(&dir);
+        let prior = verify_if_changed(&tool, None).unwrap().unwrap();
+        assert_eq!(runs(&dir), 1);
+
+        assert!(verify_if_changed(&tool, Some(&prior)).unwrap().is_none());
+        assert_eq!(runs(&dir), 1);
+
+        write_script(&tool, "echo tool 1.0.1-patched");
+        let fresh = verify_if_changed(&tool, Some(&prior)).unwrap().unwrap();
+        assert_eq!(fresh.version_report(), "tool 1.0.1-patched");
+        assert!(verify_if_changed(&tool, Some(&fresh)).unwrap().is_none());
+
+        // Bins that weren't verified from the file never count as unchanged
+        let unverified = ExecutableBin::from_parts(tool.clone(), "tool 1.0.1-patched".into(), None);
+        assert!(
+            verify_if_changed(&tool, Some(&unverified))
+                .unwrap()
+                .is_some()
+        );
+    }
+}
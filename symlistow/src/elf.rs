@@ -0,0 +1,440 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use semver::Version;
+
+use crate::ExecutableBin;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+
+/// `sh_type` of a full symbol table, as `strip` removes.
+const SHT_SYMTAB: u32 = 2;
+
+/// The most section name bytes read, well past any real `.shstrtab`.
+const MAX_SECTION_NAMES: u64 = 1024 * 1024;
+
+/// A CPU architecture, as recorded in an ELF header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    RiscV32,
+    RiscV64,
+    PowerPc,
+    PowerPc64,
+    S390x,
+    Mips,
+    Sparc64,
+    LoongArch64,
+    /// Any other `e_machine` value.
+    Other(u16),
+}
+
+/// The identifying fields of an ELF header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ElfHeader {
+    /// `EI_CLASS`: `1` for 32-bit, `2` for 64-bit.
+    pub(crate) class: u8,
+    /// `EI_DATA`: `1` for little-endian, `2` for big-endian.
+    pub(crate) data: u8,
+    /// `e_machine`, e.g. `62` for x86-64.
+    pub(crate) machine: u16,
+}
+
+impl ElfHeader {
+    /// Reads the header of the file at `path`, or `None` if it isn't ELF.
+    pub(crate) fn read(path: &Path) -> io::Result<Option<Self>> {
+        let mut ident = [0u8; 20];
+        let mut file = File::open(path)?;
+        match file.read_exact(&mut ident) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        Ok(Self::parse(&ident))
+    }
+
+    /// Parses the first 20 bytes of an ELF file.
+    fn parse(ident: &[u8; 20]) -> Option<Self> {
+        if ident[..4] != ELF_MAGIC {
+            return None;
+        }
+        let (class, data) = (ident[4], ident[5]);
+        let machine = [ident[18], ident[19]];
+        let machine = match data {
+            1 => u16::from_le_bytes(machine),
+            2 => u16::from_be_bytes(machine),
+            _ => return None,
+        };
+        Some(Self {
+            class,
+            data,
+            machine,
+        })
+    }
+
+    /// Whether the ELF file at `path`, with this header, has a symbol table or
+    /// any DWARF debug section.
+    fn has_symbols(&self, path: &Path) -> io::Result<bool> {
+        let mut file = File::open(path)?;
+        let wide = self.class == 2;
+        // e_shoff, then e_shentsize, e_shnum and e_shstrndx
+        let (shoff_at, shentsize_at) = if wide { (0x28, 0x3a) } else { (0x20, 0x2e) };
+        let mut header = [0u8; 0x40];
+        file.read_exact(&mut header[..shentsize_at + 6])?;
+        let shoff = self.word(&header[shoff_at..], wide);
+        let entry_size = u64::from(self.half(&header[shentsize_at..]));
+        let count = u64::from(self.half(&header[shentsize_at + 2..]));
+        let names_index = u64::from(self.half(&header[shentsize_at + 4..]));
+        // sh_type, then sh_offset and sh_size
+        let (offset_at, min_entry_size) = if wide { (0x18, 0x28) } else { (0x10, 0x18) };
+        if shoff == 0 || entry_size < min_entry_size {
+            return Ok(false);
+        }
+
+        // Read through `take` so a corrupt count can't make us allocate gigabytes
+        let mut table = Vec::new();
+        file.seek(SeekFrom::Start(shoff))?;
+        (&mut file)
+            .take(entry_size * count)
+            .read_to_end(&mut table)?;
+        let sections = table
+            .chunks_exact(entry_size as usize)
+            .map(|entry| {
+                let name = self.u32(&entry[0..]);
+                let kind = self.u32(&entry[4..]);
+                let size_at = offset_at + if wide { 8 } else { 4 };
+                let offset = self.word(&entry[offset_at..], wide);
+                let size = self.word(&entry[size_at..], wide);
+                (name, kind, offset, size)
+            })
+            .collect::<Vec<_>>();
+        if sections.iter().any(|&(_, kind, _, _)| kind == SHT_SYMTAB) {
+            return Ok(true);
+        }
+
+        let Some(&(_, _, names_offset, names_size)) = sections.get(names_index as usize) else {
+            return Ok(false);
+        };
+        let mut names = Vec::new();
+        file.seek(SeekFrom::Start(names_offset))?;
+        file.take(names_size.min(MAX_SECTION_NAMES))
+            .read_to_end(&mut names)?;
+        Ok(sections.iter().any(|&(name, _, _, _)| {
+            let name = names.get(name as usize..).unwrap_or_default();
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            is_debug_section(name)
+        }))
+    }
+
+    fn half(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        if self.data == 2 {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.data == 2 {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    /// An address-sized field: 8 bytes in 64-bit files, 4 bytes otherwise.
+    fn word(&self, bytes: &[u8], wide: bool) -> u64 {
+        if !wide {
+            return u64::from(self.u32(bytes));
+        }
+        let bytes: [u8; 8] = bytes[..8].try_into().unwrap_or_default();
+        if self.data == 2 {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    }
+
+    /// The architecture the binary was built for.
+    pub(crate) fn arch(&self) -> Arch {
+        match (self.machine, self.class) {
+            (3, _) => Arch::X86,
+            (62, _) => Arch::X86_64,
+            (40, _) => Arch::Arm,
+            (183, _) => Arch::Aarch64,
+            (243, 1) => Arch::RiscV32,
+            (243, _) => Arch::RiscV64,
+            (20, _) => Arch::PowerPc,
+            (21, _) => Arch::PowerPc64,
+            (22, _) => Arch::S390x,
+            (8, _) => Arch::Mips,
+            (43, _) => Arch::Sparc64,
+            (258, _) => Arch::LoongArch64,
+            (machine, _) => Arch::Other(machine),
+        }
+    }
+
+    /// The header native binaries for this host carry, if the architecture is known.
+    pub(crate) fn host() -> Option<Self> {
+        let machine = match std::env::consts::ARCH {
+            "x86" => 3,
+            "x86_64" => 62,
+            "arm" => 40,
+            "aarch64" => 183,
+            "riscv32" | "riscv64" => 243,
+            "powerpc" => 20,
+            "powerpc64" => 21,
+            "s390x" => 22,
+            "mips" | "mips64" => 8,
+            "sparc64" => 43,
+            "loongarch64" => 258,
+            _ => return None,
+        };
+        Some(Self {
+            class: if cfg!(target_pointer_width = "64") {
+                2
+            } else {
+                1
+            },
+            data: if cfg!(target_endian = "little") { 1 } else { 2 },
+            machine,
+        })
+    }
+}
+
+/// Whether `name` is a section of DWARF debug info, plain or compressed.
+///
+/// `.debug_gdb_scripts` is loaded at runtime rather than debug info, and survives
+/// `strip`, so it doesn't count.
+fn is_debug_section(name: &[u8]) -> bool {
+    (name.starts_with(b".debug_") || name.starts_with(b".zdebug_")) && name != b".debug_gdb_scripts"
+}
+
+impl ExecutableBin {
+    /// Whether the host could execute this binary, judged from its ELF header
+    /// without running it.
+    ///
+    /// An ELF binary must match the host's class (32/64-bit), endianness and
+    /// machine exactly, so e.g. a 32-bit x86 binary is rejected on x86-64. `#!`
+    /// scripts are accepted, and any other file is rejected.
+    ///
+    /// # Returns
+    /// * An [`io::ErrorKind::Unsupported`] error if the host architecture is unknown
+    pub fn is_runnable_on_host(&self) -> io::Result<bool> {
+        let host = ElfHeader::host().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unknown host architecture {}", std::env::consts::ARCH),
+            )
+        })?;
+        match ElfHeader::read(self.path())? {
+            Some(header) => Ok(header == host),
+            None => Ok(self.is_script()?.is_some()),
+        }
+    }
+
+    /// Whether the binary is stripped, i.e. has neither a symbol table nor any
+    /// debug sections, so backtraces from it will lack function names.
+    ///
+    /// Only the section headers are read. A file that isn't ELF is an
+    /// [`io::ErrorKind::InvalidData`] error.
+    pub fn is_stripped(&self) -> io::Result<bool> {
+        let header = ElfHeader::read(self.path())?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not an ELF file", self.path().display()),
+            )
+        })?;
+        Ok(!header.has_symbols(self.path())?)
+    }
+
+    /// The binary's name, parsed version and architecture in one call.
+    ///
+    /// The name is [`ExecutableBin::name`]; the version is `None` when the report
+    /// doesn't parse and the architecture is `None` for anything but ELF files.
+    pub fn identity(&self) -> io::Result<(String, Option<Version>, Option<Arch>)> {
+        let arch = ElfHeader::read(self.path())?.map(|header| header.arch());
+        Ok((self.name().into_owned(), self.version().ok(), arch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    /// A 64-byte ELF file header with the given identifying fields.
+    fn elf_header(header: ElfHeader) -> Vec<u8> {
+        let mut bytes = vec![0; 0x40];
+        bytes[..4].copy_from_slice(&ELF_MAGIC);
+        bytes[4] = header.class;
+        bytes[5] = header.data;
+        bytes[6] = 1;
+        let machine = if header.data == 2 {
+            header.machine.to_be_bytes()
+        } else {
+            header.machine.to_le_bytes()
+        };
+        bytes[18..20].copy_from_slice(&machine);
+        bytes
+    }
+
+    /// A bin for an ELF file at `dir/name` holding only `header`.
+    fn elf_file(dir: &TempDir, name: &str, header: ElfHeader) -> ExecutableBin {
+        fs::write(dir.join(name), elf_header(header)).unwrap();
+        ExecutableBin::new_unverified(dir.join(name), String::new())
+    }
+
+    /// A little-endian ELF64 file with a section of each `(name, sh_type)`, a
+    /// null section first and a `.shstrtab` naming them last.
+    fn elf64_with_sections(sections: &[(&str, u32)]) -> Vec<u8> {
+        let mut bytes = elf_header(ElfHeader {
+            class: 2,
+            data: 1,
+            machine: 62,
+        });
+        let sections = [("", 0)]
+            .iter()
+            .chain(sections)
+            .chain(&[(".shstrtab", 3)])
+            .copied()
+            .collect::<Vec<_>>();
+        let names_offset = bytes.len() as u64;
+        let mut name_at = Vec::new();
+        for (name, _) in &sections {
+            name_at.push((bytes.len() as u64 - names_offset) as u32);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(0);
+        }
+        let names_size = bytes.len() as u64 - names_offset;
+        let shoff = bytes.len() as u64;
+        for (index, (_, kind)) in sections.iter().enumerate() {
+            let mut entry = [0u8; 0x40];
+            entry[0..4].copy_from_slice(&name_at[index].to_le_bytes());
+            entry[4..8].copy_from_slice(&kind.to_le_bytes());
+            if index == sections.len() - 1 {
+                entry[0x18..0x20].copy_from_slice(&names_offset.to_le_bytes());
+                entry[0x20..0x28].copy_from_slice(&names_size.to_le_bytes());
+            }
+            bytes.extend_from_slice(&entry);
+        }
+        bytes[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        bytes[0x3a..0x3c].copy_from_slice(&0x40u16.to_le_bytes());
+        bytes[0x3c..0x3e].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+        bytes[0x3e..0x40].copy_from_slice(&(sections.len() as u16 - 1).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn is_runnable_on_host_compares_class_endianness_and_machine() {
+        let dir = TempDir::new();
+        let host = ElfHeader::host().unwrap();
+        assert!(
+            elf_file(&dir, "native", host)
+                .is_runnable_on_host()
+                .unwrap()
+        );
+
+        let other_class = ElfHeader {
+            class: 3 - host.class,
+            ..host
+        };
+        let other_endian = ElfHeader {
+            data: 3 - host.data,
+            ..host
+        };
+        let other_machine = ElfHeader {
+            machine: if host.machine == 62 { 183 } else { 62 },
+            ..host
+        };
+        for (name, header) in [
+            ("other-class", other_class),
+            ("other-endian", other_endian),
+            ("other-machine", other_machine),
+        ] {
+            assert!(
+                !elf_file(&dir, name, header).is_runnable_on_host().unwrap(),
+                "{name}"
+            );
+        }
+
+        let script = write_script(&dir.join("script"), "echo hi");
+        let script = ExecutableBin::new_unverified(script, String::new());
+        assert!(script.is_runnable_on_host().unwrap());
+        fs::write(dir.join("text"), "plain text, long enough to hold a header").unwrap();
+        let text = ExecutableBin::new_unverified(dir.join("text"), String::new());
+        assert!(!text.is_runnable_on_host().unwrap());
+    }
+
+    #[test]
+    fn identity_combines_name_version_and_arch() {
+        let dir = TempDir::new();
+        let header = ElfHeader {
+            class: 2,
+            data: 1,
+            machine: 183,
+        };
+        fs::write(dir.join("rg"), elf_header(header)).unwrap();
+        let bin = ExecutableBin::new_unverified(dir.join("rg"), "ripgrep 14.1.0\n+SIMD".into());
+        assert_eq!(
+            bin.identity().unwrap(),
+            (
+                "rg".to_string(),
+                Some(Version::new(14, 1, 0)),
+                Some(Arch::Aarch64)
+            )
+        );
+
+        let script = write_script(&dir.join("tool"), "echo hi");
+        let bin = ExecutableBin::new_unverified(script, "tool (dev build)".into());
+        assert_eq!(bin.identity().unwrap(), ("tool".to_string(), None, None));
+        let missing = ExecutableBin::new_unverified(dir.join("missing"), String::new());
+        assert!(missing.identity().is_err());
+    }
+
+    #[test]
+    fn is_stripped_looks_for_a_symbol_table_or_debug_sections() {
+        let dir = TempDir::new();
+        let stripped = |name: &str, sections: &[(&str, u32)]| {
+            fs::write(dir.join(name), elf64_with_sections(sections)).unwrap();
+            ExecutableBin::new_unverified(dir.join(name), String::new())
+                .is_stripped()
+                .unwrap()
+        };
+        assert!(stripped("bare", &[(".text", 1)]));
+        assert!(stripped(
+            "gdb-scripts",
+            &[(".text", 1), (".debug_gdb_scripts", 1)]
+        ));
+        assert!(!stripped("symtab", &[(".text", 1), (".symtab", 2)]));
+        assert!(!stripped("debug", &[(".text", 1), (".debug_info", 1)]));
+        assert!(!stripped("compressed", &[(".zdebug_line", 1)]));
+
+        let header_only = ElfHeader {
+            class: 2,
+            data: 1,
+            machine: 62,
+        };
+        assert!(
+            elf_file(&dir, "no-sections", header_only)
+                .is_stripped()
+                .unwrap()
+        );
+        let script = ExecutableBin::new_unverified(
+            write_script(&dir.join("script"), "echo hi"),
+            String::new(),
+        );
+        assert_eq!(
+            script.is_stripped().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
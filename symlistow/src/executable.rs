@@ -0,0 +1,645 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use semver::{BuildMetadata, Version, VersionReq};
+
+use crate::cache::FileStamp;
+use crate::runner::{CommandRunner, SystemRunner, output_with_input, output_with_timeout};
+use crate::version::{
+    DEFAULT_PLACEHOLDER_PATTERNS, VersionMismatch, VersionParseError, find_commit_hash,
+    is_placeholder, numeric_components, parse_version_report,
+};
+use crate::{ExecutableVerificationError, VerifyOptions, verify_output_via};
+
+/// How long [`ExecutableBin::capture_help`] waits for `--help` to finish.
+pub const HELP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Err Type for a smoke test that didn't produce the expected output
+#[derive(Debug, thiserror::Error)]
+pub enum SmokeTestError {
+    #[error("Smoke test did not run: {0}")]
+    Run(#[from] io::Error),
+    #[error("Smoke test exited with {0}")]
+    Failed(ExitStatus),
+    #[error("Smoke test output did not contain {expected:?}, got {stdout:?}")]
+    UnexpectedOutput { expected: String, stdout: String },
+}
+
+/// A binary that has been verified to exist and answer `--version`.
+///
+/// Bins sort canonically rather than lexically by path:
+/// 1. by basename, so copies of the same tool are grouped together;
+/// 2. by parsed semver version, newest first; bins whose report has no
+///    parseable version come after all versioned bins of that name;
+/// 3. by full path, as a tiebreaker;
+/// 4. by the raw version report, so the order agrees with `Eq`.
+///
+/// Two bins are equal when their path and version report are.
+#[derive(Debug, Clone)]
+pub struct ExecutableBin {
+    path: PathBuf,
+    version_report: String,
+    /// The file's size and mtime when it was verified, if they could be read.
+    stamp: Option<FileStamp>,
+}
+
+impl ExecutableBin {
+    /// Verifies the binary at `path` and captures its version report.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, ExecutableVerificationError> {
+        Self::new_with(path, &VerifyOptions::default())
+    }
+
+    /// Like [`ExecutableBin::new`], probing the version as set by `options`.
+    pub fn new_with(
+        path: impl Into<PathBuf>,
+        options: &VerifyOptions,
+    ) -> Result<Self, ExecutableVerificationError> {
+        Self::new_with_runner(path, options, &SystemRunner)
+    }
+
+    /// Like [`ExecutableBin::new_with`], running the version command through
+    /// `runner`, e.g. a [`crate::WasmRunner`] for `.wasm` modules.
+    ///
+    /// The verified [`ExecutableBin::path`] is always `path`, however the runner
+    /// actually executes it.
+    pub fn new_with_runner(
+        path: impl Into<PathBuf>,
+        options: &VerifyOptions,
+        runner: &dyn CommandRunner,
+    ) -> Result<Self, ExecutableVerificationError> {
+        let path = path.into();
+        // Stamped before running, so a change made during the probe isn't missed
+        let stamp = FileStamp::of(&path).ok();
+        let version_report = verify_output_via(&path, options, runner)?.version;
+        Ok(Self {
+            path,
+            version_report,
+            stamp,
+        })
+    }
+
+    /// Builds a bin from a path and version report without checking either.
+    ///
+    /// Nothing is run and the path isn't even required to exist. Use this only for
+    /// results verified out of band, such as ones reloaded from a cache file. A bin
+    /// built this way always counts as changed for [`crate::verify_if_changed`].
+    pub fn new_unverified(path: PathBuf, version_report: String) -> Self {
+        Self::from_parts(path, version_report, None)
+    }
+
+    pub(crate) fn from_parts(
+        path: PathBuf,
+        version_report: String,
+        stamp: Option<FileStamp>,
+    ) -> Self {
+        Self {
+            path,
+            version_report,
+            stamp,
+        }
+    }
+
+    pub(crate) fn stamp(&self) -> Option<FileStamp> {
+        self.stamp
+    }
+
+    /// The path the binary was verified at.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The trimmed output of the binary's `--version` call.
+    pub fn version_report(&self) -> &str {
+        &self.version_report
+    }
+
+    /// The binary's file name, falling back to the full path.
+    pub fn name(&self) -> Cow<'_, str> {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_else(|| self.path.to_string_lossy())
+    }
+
+    /// The semver version parsed from the version report.
+    pub fn version(&self) -> Result<Version, VersionParseError> {
+        parse_version_report(&self.version_report)
+    }
+
+    /// Checks that the version report is exactly `expected`, e.g. to pin a tool's
+    /// output in a test.
+    ///
+    /// The report is already trimmed, so `expected` should be too. A mismatch
+    /// reports both texts and the first line where they differ.
+    pub fn assert_version_matches(&self, expected: &str) -> Result<(), VersionMismatch> {
+        VersionMismatch::check(expected, &self.version_report)
+    }
+
+    /// The build metadata of the parsed version, e.g. `gabc1234` for `1.2.3+gabc1234`.
+    ///
+    /// `None` when the version report doesn't parse or carries no build metadata.
+    pub fn build_metadata(&self) -> Option<String> {
+        let version = self.version().ok()?;
+        (!version.build.is_empty()).then(|| version.build.to_string())
+    }
+
+    /// The git commit hash the version report is stamped with, lowercased, e.g.
+    /// `abc1234` for `1.2.3+gabc1234` or `commit: abc1234`.
+    ///
+    /// `None` when the report has no plausible hash.
+    pub fn commit_hash(&self) -> Option<String> {
+        find_commit_hash(&self.version_report)
+    }
+
+    /// The first run of dot-separated integers in the version report.
+    ///
+    /// Unlike [`ExecutableBin::version`] this accepts any number of components,
+    /// e.g. `1.21` or `10.0.19041.1`.
+    pub fn version_numbers(&self) -> Vec<u64> {
+        numeric_components(&self.version_report)
+    }
+
+    /// The [`ExecutableBin::version_numbers`] as an `X.Y.Z` semver string.
+    ///
+    /// Missing components are zero, so `v1.2` gives `1.2.0`, and components past
+    /// the patch are kept as build metadata, so `10.0.19041.1` gives
+    /// `10.0.19041+1`. `None` when the report has no version numbers.
+    pub fn canonical_version(&self) -> Option<String> {
+        let numbers = self.version_numbers();
+        let component = |i: usize| numbers.get(i).copied().unwrap_or(0);
+        let (major, minor, patch) = (*numbers.first()?, component(1), component(2));
+        let mut version = Version::new(major, minor, patch);
+        if numbers.len() > 3 {
+            let extra = numbers[3..].iter().map(u64::to_string).collect::<Vec<_>>();
+            version.build = BuildMetadata::new(&extra.join(".")).ok()?;
+        }
+        Some(version.to_string())
+    }
+
+    /// Whether the version report looks like an un-stamped build, such as an empty
+    /// report, `0.0.0-dev`, `unknown` or `HEAD`.
+    pub fn is_placeholder_version(&self) -> bool {
+        self.is_placeholder_version_in(DEFAULT_PLACEHOLDER_PATTERNS)
+    }
+
+    /// Like [`ExecutableBin::is_placeholder_version`], with a custom pattern set.
+    pub fn is_placeholder_version_in(&self, patterns: &[&str]) -> bool {
+        is_placeholder(&self.version_report, patterns)
+    }
+
+    /// Whether the binary's version matches any requirement in `denied`.
+    ///
+    /// A report without a parseable version is an error rather than "not denied".
+    pub fn is_denied(&self, denied: &[VersionReq]) -> Result<bool, VersionParseError> {
+        let version = self.version()?;
+        Ok(denied.iter().any(|req| req.matches(&version)))
+    }
+
+    /// Runs the binary with `--help` and returns its trimmed output.
+    ///
+    /// Help on stdout is preferred, falling back to stderr for tools that print it
+    /// there. Stdin is closed and the call is killed after [`HELP_TIMEOUT`], so
+    /// tools that wait for input can't hang it. Nothing is cached.
+    pub fn capture_help(&self) -> io::Result<String> {
+        let output = output_with_timeout(Command::new(&self.path).arg("--help"), HELP_TIMEOUT)?;
+        let text = if output.stdout.iter().all(u8::is_ascii_whitespace) {
+            output.stderr
+        } else {
+            output.stdout
+        };
+        Ok(String::from_utf8_lossy(&text).trim().to_string())
+    }
+
+    /// The applets of a multi-call binary such as busybox, empty for anything else.
+    ///
+    /// The binary is run with `--list`, under the same timeout as
+    /// [`ExecutableBin::capture_help`]. It counts as multi-call when that succeeds
+    /// and prints at least two lines, each a single word naming an applet.
+    pub fn applets(&self) -> io::Result<Vec<String>> {
+        let output = output_with_timeout(Command::new(&self.path).arg("--list"), HELP_TIMEOUT)?;
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+        let listing = String::from_utf8_lossy(&output.stdout);
+        let names = listing
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let is_applet = |name: &&str| {
+            name.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '['))
+        };
+        if names.len() < 2 || !names.iter().all(is_applet) {
+            return Ok(Vec::new());
+        }
+        Ok(names.into_iter().map(str::to_string).collect())
+    }
+
+    /// Whether this is a multi-call binary whose behavior depends on the name it
+    /// is invoked as, see [`ExecutableBin::applets`].
+    pub fn is_multicall(&self) -> io::Result<bool> {
+        Ok(!self.applets()?.is_empty())
+    }
+
+    /// Runs the binary as a functional check beyond `--version`, e.g. `jq .` fed
+    /// `{}`, and checks that its stdout contains `expected_stdout`.
+    ///
+    /// Stdin is closed unless `stdin` is given, and the run is killed after
+    /// [`HELP_TIMEOUT`], failing with [`SmokeTestError::Run`].
+    pub fn smoke_test(
+        &self,
+        args: &[&str],
+        stdin: Option<&[u8]>,
+        expected_stdout: &str,
+    ) -> Result<(), SmokeTestError> {
+        let output = output_with_input(Command::new(&self.path).args(args), stdin, HELP_TIMEOUT)?;
+        if !output.status.success() {
+            return Err(SmokeTestError::Failed(output.status));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !stdout.contains(expected_stdout) {
+            return Err(SmokeTestError::UnexpectedOutput {
+                expected: expected_stdout.to_string(),
+                stdout: stdout.into_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs the verified binary with `args`, inheriting stdio.
+    ///
+    /// The command is spawned from the exact path that was verified, so the
+    /// program that runs is the one whose version was checked.
+    ///
+    /// # Arguments
+    /// * `args` - Arguments passed through to the binary
+    /// * `replace` - Replace the current process via `exec` instead of spawning a child;
+    ///   signals and the exit code then belong to the binary itself
+    ///
+    /// # Returns
+    /// * The child's exit status, or the error that prevented it from running.
+    ///   With `replace`, this only returns if `exec` failed.
+    pub fn exec_verified(&self, args: &[&str], replace: bool) -> io::Result<ExitStatus> {
+        let mut command = Command::new(&self.path);
+        command.args(args);
+        if replace {
+            return Err(command.exec());
+        }
+        command.status()
+    }
+}
+
+/// Keeps verifying the binary at `path` until it succeeds or `total_timeout` elapses.
+///
+/// Meant for tools that were just installed and need a moment before `--version`
+/// works. Any failure, not only transient io errors, is retried.
+///
+/// # Arguments
+/// * `path` - The file path to the binary
+/// * `total_timeout` - How long to keep trying overall
+/// * `interval` - How long to sleep between attempts
+///
+/// # Returns
+/// * The first successful verification, or the error from the last attempt
+pub fn verify_binary_eventually(
+    path: &Path,
+    total_timeout: Duration,
+    interval: Duration,
+) -> Result<ExecutableBin, ExecutableVerificationError> {
+    let deadline = Instant::now() + total_timeout;
+    loop {
+        let error = match ExecutableBin::new(path) {
+            Ok(bin) => return Ok(bin),
+            Err(e) => e,
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(error);
+        }
+        thread::sleep(interval.min(remaining));
+    }
+}
+
+/// Verifies `path` once per version flag and keeps the most informative report.
+///
+/// Successful reports that contain a parseable semver version beat those that
+/// don't; among equals the longer report wins, then the earlier flag.
+///
+/// # Returns
+/// * The best verification, or every flag's error if none succeeded
+pub fn verify_best_of(
+    path: &Path,
+    flags: &[&str],
+) -> Result<ExecutableBin, Vec<ExecutableVerificationError>> {
+    let mut best: Option<ExecutableBin> = None;
+    let mut errors = Vec::new();
+    for flag in flags {
+        let options = VerifyOptions {
+            version_args: vec![flag.to_string()],
+            ..VerifyOptions::default()
+        };
+        match ExecutableBin::new_with(path, &options) {
+            Ok(bin) => {
+                if best.as_ref().is_none_or(|best| score(&bin) > score(best)) {
+                    best = Some(bin);
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    best.ok_or(errors)
+}
+
+fn score(bin: &ExecutableBin) -> (bool, usize) {
+    (bin.version().is_ok(), bin.version_report().len())
+}
+
+impl PartialEq for ExecutableBin {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.version_report == other.version_report
+    }
+}
+
+impl Eq for ExecutableBin {}
+
+impl Ord for ExecutableBin {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name()
+            .cmp(&other.name())
+            // Reversed so that newer versions, and any version over none, sort first
+            .then_with(|| other.version().ok().cmp(&self.version().ok()))
+            .then_with(|| self.path.cmp(&other.path))
+            .then_with(|| self.version_report.cmp(&other.version_report))
+    }
+}
+
+impl PartialOrd for ExecutableBin {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn exec_verified_passes_args_and_returns_the_exit_status() {
+        let dir = TempDir::new();
+        let tool = write_script(
+            &dir.join("tool"),
+            r#"[ "$1" = --version ] && { echo tool 1.0.0; exit 0; }
+[ "$*" = "a b" ] && exit 3
+exit 1"#,
+        );
+        let bin = ExecutableBin::new(&tool).unwrap();
+        assert_eq!(
+            bin.exec_verified(&["a", "b"], false).unwrap().code(),
+            Some(3)
+        );
+        assert_eq!(bin.exec_verified(&[], false).unwrap().code(), Some(1));
+    }
+
+    #[test]
+    fn bins_sort_by_name_then_newest_version_then_path() {
+        let dir = TempDir::new();
+        let bin = |path: &str, report: &str| {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            ExecutableBin::new(write_script(&path, &format!("echo '{report}'"))).unwrap()
+        };
+        let mut bins = [
+            bin("c/git", "git version unknown"),
+            bin("d/git", "git version 2.40.0"),
+            bin("z/cargo", "cargo 1.75.0"),
+            bin("a/git", "git version 2.40.0 (patched)"),
+            bin("a/git", "git version 2.40.0"),
+            bin("e/git", "git version 2.43.0"),
+        ];
+        bins.sort();
+        let order = bins
+            .iter()
+            .map(|bin| {
+                let path = bin.path().strip_prefix(dir.path()).unwrap();
+                (path.to_str().unwrap(), bin.version_report())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                ("z/cargo", "cargo 1.75.0"),
+                ("e/git", "git version 2.43.0"),
+                ("a/git", "git version 2.40.0"),
+                ("a/git", "git version 2.40.0 (patched)"),
+                ("d/git", "git version 2.40.0"),
+                ("c/git", "git version unknown"),
+            ]
+        );
+    }
+
+    #[test]
+    fn is_denied_matches_denied_versions_only() {
+        let denied = [VersionReq::parse("=1.2.3").unwrap()];
+        let dir = TempDir::new();
+        let bin = |report: &str| {
+            let path = write_script(&dir.join("tool"), &format!("echo '{report}'"));
+            ExecutableBin::new(path).unwrap()
+        };
+        assert!(bin("tool 1.2.3").is_denied(&denied).unwrap());
+        assert!(!bin("tool 1.2.4").is_denied(&denied).unwrap());
+        assert!(bin("tool dev build").is_denied(&denied).is_err());
+    }
+
+    #[test]
+    fn verify_binary_eventually_retries_until_the_tool_works() {
+        let dir = TempDir::new();
+        let ready = dir.join("ready");
+        let tool = write_script(
+            &dir.join("tool"),
+            &format!("[ -e '{}' ] || exit 1\necho tool 1.0.0", ready.display()),
+        );
+        let started = Instant::now();
+        let installer = thread::spawn(move || {
+            thread::sleep(Duration::from_secs(1));
+            std::fs::write(ready, "").unwrap();
+        });
+        let bin =
+            verify_binary_eventually(&tool, Duration::from_secs(10), Duration::from_millis(50))
+                .unwrap();
+        installer.join().unwrap();
+        assert_eq!(bin.version_report(), "tool 1.0.0");
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn verify_binary_eventually_gives_up_with_the_last_error() {
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("tool"), "exit 1");
+        let started = Instant::now();
+        let error =
+            verify_binary_eventually(&tool, Duration::from_millis(300), Duration::from_millis(50))
+                .unwrap_err();
+        assert!(matches!(
+            error,
+            ExecutableVerificationError::VersionCallFail(_)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn verify_best_of_prefers_a_parseable_version_over_longer_noise() {
+        let dir = TempDir::new();
+        let tool = write_script(
+            &dir.join("tool"),
+            r#"case "$1" in
+    -V) echo "tool: unrecognized option, see tool --help for the list of options" ;;
+    --version) echo "tool 1.2.3" ;;
+    *) exit 2 ;;
+esac"#,
+        );
+        let best = verify_best_of(&tool, &["-V", "--version", "-x"]).unwrap();
+        assert_eq!(best.version_report(), "tool 1.2.3");
+
+        let errors = verify_best_of(&tool, &["-x", "-y"]).unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn capture_help_returns_the_trimmed_help_text() {
+        let dir = TempDir::new();
+        let tool = write_script(
+            &dir.join("tool"),
+            r#"case "$1" in
+    --version) echo "tool 1.0.0" ;;
+    --help) cat; printf '\n  tool - does things\n\nUsage: tool [options]\n\n' ;;
+esac"#,
+        );
+        let bin = ExecutableBin::new(&tool).unwrap();
+        assert_eq!(
+            bin.capture_help().unwrap(),
+            "tool - does things\n\nUsage: tool [options]"
+        );
+
+        let quiet = write_script(
+            &dir.join("quiet"),
+            r#"[ "$1" = --help ] && { echo "usage: quiet" >&2; exit 1; }
+echo "quiet 2.0""#,
+        );
+        let bin = ExecutableBin::new(&quiet).unwrap();
+        assert_eq!(bin.capture_help().unwrap(), "usage: quiet");
+    }
+
+    #[test]
+    fn new_unverified_keeps_what_it_was_given() {
+        let bin = ExecutableBin::new_unverified(
+            "/nonexistent/bin/tool-1".into(),
+            "  tool 1.2.3 (raw)\n".into(),
+        );
+        assert_eq!(bin.path(), Path::new("/nonexistent/bin/tool-1"));
+        assert_eq!(bin.version_report(), "  tool 1.2.3 (raw)\n");
+        assert_eq!(bin.name(), "tool-1");
+        assert_eq!(bin.stamp(), None);
+    }
+
+    #[test]
+    fn build_metadata_is_the_plus_suffix_of_the_version() {
+        let bin = |report: &str| ExecutableBin::new_unverified("/bin/tool".into(), report.into());
+        assert_eq!(
+            bin("tool 1.2.3+gabc1234").build_metadata().as_deref(),
+            Some("gabc1234")
+        );
+        assert_eq!(
+            bin("tool 2.0.0-rc.1+build.5.dirty")
+                .build_metadata()
+                .as_deref(),
+            Some("build.5.dirty")
+        );
+        assert_eq!(bin("tool 1.2.3").build_metadata(), None);
+        assert_eq!(bin("tool version unknown").build_metadata(), None);
+    }
+
+    #[test]
+    fn canonical_version_pads_and_keeps_extra_components_as_build_metadata() {
+        let canonical = |report: &str| {
+            ExecutableBin::new_unverified("/bin/tool".into(), report.into()).canonical_version()
+        };
+        assert_eq!(canonical("tool v1.2").as_deref(), Some("1.2.0"));
+        assert_eq!(canonical("go version go1").as_deref(), Some("1.0.0"));
+        assert_eq!(canonical("tool 2.43.0").as_deref(), Some("2.43.0"));
+        assert_eq!(
+            canonical("Version 10.0.19041.1 (x64)").as_deref(),
+            Some("10.0.19041+1")
+        );
+        assert_eq!(canonical("tool 1.2.0.0.7").as_deref(), Some("1.2.0+0.7"));
+        assert_eq!(canonical("tool version unknown"), None);
+    }
+
+    #[test]
+    fn applets_lists_what_a_multicall_binary_provides() {
+        let dir = TempDir::new();
+        let multicall = write_script(
+            &dir.join("box"),
+            r#"case "$1" in
+    --version) echo "box 1.36.1" ;;
+    --list) printf '%s\n' '[' ls cat sha256sum run-parts ;;
+esac"#,
+        );
+        let bin = ExecutableBin::new(&multicall).unwrap();
+        assert_eq!(
+            bin.applets().unwrap(),
+            ["[", "ls", "cat", "sha256sum", "run-parts"]
+        );
+        assert!(bin.is_multicall().unwrap());
+
+        for (name, body) in [
+            ("no-list", r#"[ "$1" = --list ] && exit 1; echo "tool 1.0""#),
+            (
+                "prose",
+                r#"[ "$1" = --list ] && { echo "Listing:"; echo "two words"; }; echo "tool 1.0""#,
+            ),
+            (
+                "single",
+                r#"[ "$1" = --list ] && { echo "only"; exit 0; }; echo "tool 1.0""#,
+            ),
+        ] {
+            let bin = ExecutableBin::new(write_script(&dir.join(name), body)).unwrap();
+            assert!(bin.applets().unwrap().is_empty(), "{name}");
+            assert!(!bin.is_multicall().unwrap(), "{name}");
+        }
+    }
+
+    #[test]
+    fn smoke_test_feeds_stdin_and_checks_stdout() {
+        let dir = TempDir::new();
+        let path = write_script(
+            &dir.join("jq"),
+            "case \"$1\" in\n--version) echo jq-1.7.1 ;;\n.) cat ;;\n*) exit 4 ;;\nesac",
+        );
+        let jq = ExecutableBin::new(&path).unwrap();
+
+        jq.smoke_test(&["."], Some(br#"{"a": 1}"#), r#""a": 1"#)
+            .unwrap();
+        assert!(matches!(
+            jq.smoke_test(&["."], None, r#""a""#),
+            Err(SmokeTestError::UnexpectedOutput { expected, stdout })
+                if expected == r#""a""# && stdout.is_empty()
+        ));
+        assert!(matches!(
+            jq.smoke_test(&["--bogus"], None, ""),
+            Err(SmokeTestError::Failed(status)) if status.code() == Some(4)
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            jq.smoke_test(&["."], None, ""),
+            Err(SmokeTestError::Run(e)) if e.kind() == io::ErrorKind::NotFound
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::ExecutableBin;
+
+/// Built-in basename prefixes and the toolchain family they belong to.
+///
+/// A prefix matches a basename equal to it or continuing with a non-letter, so
+/// `clang` covers `clang-17` and `clang++` but not `clangify`.
+pub const DEFAULT_FAMILIES: &[(&str, &str)] = &[
+    ("clang", "llvm"),
+    ("clangd", "llvm"),
+    ("lld", "llvm"),
+    ("lldb", "llvm"),
+    ("llvm", "llvm"),
+    ("gcc", "gnu"),
+    ("g++", "gnu"),
+    ("cargo", "rust"),
+    ("rustc", "rust"),
+    ("rustdoc", "rust"),
+    ("rustfmt", "rust"),
+    ("rustup", "rust"),
+    ("python", "python"),
+    ("pip", "python"),
+    ("node", "node"),
+    ("npm", "node"),
+    ("npx", "node"),
+    ("go", "go"),
+    ("gofmt", "go"),
+    ("java", "java"),
+    ("javac", "java"),
+    ("jar", "java"),
+];
+
+impl ExecutableBin {
+    /// The toolchain family of the binary according to [`DEFAULT_FAMILIES`].
+    pub fn family(&self) -> Option<&'static str> {
+        self.family_in(DEFAULT_FAMILIES)
+    }
+
+    /// The toolchain family of the binary according to `families`.
+    ///
+    /// When several prefixes match, the longest one wins.
+    pub fn family_in(&self, families: &[(&str, &'static str)]) -> Option<&'static str> {
+        let name = self.name();
+        families
+            .iter()
+            .filter(|(prefix, _)| {
+                name.strip_prefix(prefix)
+                    .is_some_and(|rest| !rest.starts_with(|c: char| c.is_ascii_alphabetic()))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, family)| *family)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(path: &str) -> Option<&'static str> {
+        ExecutableBin::new_unverified(path.into(), String::new()).family()
+    }
+
+    #[test]
+    fn family_matches_known_prefixes_only() {
+        assert_eq!(family("/usr/bin/clang-17"), Some("llvm"));
+        assert_eq!(family("/usr/bin/clang++"), Some("llvm"));
+        assert_eq!(family("/usr/bin/clangd"), Some("llvm"));
+        assert_eq!(family("/usr/bin/python3.12"), Some("python"));
+        assert_eq!(family("/usr/bin/clangify"), None);
+        assert_eq!(family("/usr/local/bin/symlistow-unknown"), None);
+
+        let bin = ExecutableBin::new_unverified("/opt/bin/zig-0.11".into(), String::new());
+        assert_eq!(bin.family_in(&[("zig", "zig")]), Some("zig"));
+        assert_eq!(
+            bin.family_in(&[("zig", "zig"), ("zig-0", "legacy")]),
+            Some("legacy")
+        );
+    }
+}
@@ -0,0 +1,563 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use semver::{Version, VersionReq};
+use sha2::{Digest, Sha256};
+
+use crate::search::{find_on_path, is_executable};
+use crate::version::VersionParseError;
+use crate::{ExecutableBin, ExecutableVerificationError, MAX_PROBE_DEPTH};
+
+/// Err Type for a script whose interpreter doesn't meet a version requirement
+#[derive(Debug, thiserror::Error)]
+pub enum InterpreterError {
+    #[error("Not a #! script: {0}")]
+    NotAScript(PathBuf),
+    #[error("Failed to read script: {0}")]
+    Read(#[from] io::Error),
+    #[error(transparent)]
+    Verification(#[from] ExecutableVerificationError),
+    #[error(transparent)]
+    Version(#[from] VersionParseError),
+    #[error("Interpreter {interpreter} is version {version}, which doesn't satisfy {required}")]
+    Unsatisfied {
+        interpreter: PathBuf,
+        version: Version,
+        required: VersionReq,
+    },
+}
+
+/// The elevated permission bits of a file's mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrivilegeBits {
+    pub setuid: bool,
+    pub setgid: bool,
+    pub sticky: bool,
+}
+
+/// Hex-encoded sha256 digest of the file at `path`.
+pub(crate) fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex_digest(hasher))
+}
+
+fn hex_digest(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// A digest identifying a whole set of tools by their fingerprints.
+///
+/// Fingerprints are sorted before hashing, so the order of `bins` doesn't matter,
+/// and since they leave out paths, the same toolchain installed in different
+/// places on different machines gives the same digest.
+pub fn toolchain_fingerprint(bins: &[ExecutableBin]) -> String {
+    let mut fingerprints = bins
+        .iter()
+        .map(ExecutableBin::fingerprint)
+        .collect::<Vec<_>>();
+    fingerprints.sort();
+    let mut hasher = Sha256::new();
+    for fingerprint in &fingerprints {
+        hasher.update(fingerprint.as_bytes());
+        hasher.update(b"\n");
+    }
+    hex_digest(hasher)
+}
+
+/// The executables directly inside `dir` that anyone may write to, i.e. with
+/// the `o+w` bit set, as anyone could swap in a binary of their own.
+///
+/// Links are followed, so a link is flagged by the file it leads to. Entries
+/// come in directory order.
+pub fn find_world_writable(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut writable = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_executable(&path) && fs::metadata(&path)?.permissions().mode() & 0o002 != 0 {
+            writable.push(path);
+        }
+    }
+    Ok(writable)
+}
+
+/// Looks up the version of the file at `path` by its sha256, without running it.
+///
+/// # Arguments
+/// * `db` - Lowercase hex sha256 digests mapped to the version they identify
+///
+/// # Returns
+/// * The version recorded for the file's digest, or `None` if it isn't in `db`
+pub fn identify_by_hash(path: &Path, db: &BTreeMap<String, String>) -> io::Result<Option<String>> {
+    Ok(db.get(&sha256_file(path)?).cloned())
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    // Step up before one-decimal rounding would print 1024.0 of the smaller unit
+    while size >= 1023.95 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Reads until `buf` is full or the reader is exhausted, returning the bytes read.
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+impl ExecutableBin {
+    /// Hex-encoded sha256 digest of the binary's contents.
+    ///
+    /// This reads the whole file.
+    pub fn sha256(&self) -> io::Result<String> {
+        sha256_file(self.path())
+    }
+
+    /// A hex sha256 digest of the binary's name and version report.
+    ///
+    /// The path and file contents are left out, so the fingerprint only changes
+    /// when the tool reports a different version.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.name().as_bytes());
+        hasher.update([0]);
+        hasher.update(self.version_report().as_bytes());
+        hex_digest(hasher)
+    }
+
+    /// The interpreter line of a `#!` script, or `None` for anything else.
+    ///
+    /// For `#!/usr/bin/env python3` this returns `/usr/bin/env python3`.
+    pub fn is_script(&self) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+        // The kernel ignores shebang lines longer than this anyway
+        BufReader::new(File::open(self.path())?.take(256)).read_until(b'\n', &mut line)?;
+        Ok(line
+            .strip_prefix(b"#!")
+            .map(|interpreter| String::from_utf8_lossy(interpreter).trim().to_string()))
+    }
+
+    /// Verifies the interpreters that running this script would go through.
+    ///
+    /// For a `#!` script this is its interpreter, then that interpreter's own
+    /// interpreter if it is a script too, and so on. An `/usr/bin/env X` line is
+    /// resolved to `X` on `$PATH`. An interpreter that runs but rejects
+    /// `--version` (e.g. `dash`) still counts as verified, with an empty report.
+    ///
+    /// # Returns
+    /// * The interpreters in the order they are invoked, empty for a non-script
+    /// * [`ExecutableVerificationError::MissingPath`] or
+    ///   [`ExecutableVerificationError::NotOnPath`] for a missing interpreter
+    /// * [`ExecutableVerificationError::ProbeDepthExceeded`] past
+    ///   [`MAX_PROBE_DEPTH`] nested interpreters
+    pub fn verify_interpreter_chain(
+        &self,
+    ) -> Result<Vec<ExecutableBin>, ExecutableVerificationError> {
+        let mut chain = Vec::new();
+        let mut current = self.path().to_path_buf();
+        while let Some(line) = ExecutableBin::new_unverified(current, String::new()).is_script()? {
+            if chain.len() >= MAX_PROBE_DEPTH {
+                return Err(ExecutableVerificationError::ProbeDepthExceeded(chain.len()));
+            }
+            let interpreter = interpreter_path(&line)?;
+            let bin = match ExecutableBin::new(&interpreter) {
+                Err(ExecutableVerificationError::VersionCallFail(_)) => {
+                    ExecutableBin::new_unverified(interpreter, String::new())
+                }
+                result => result?,
+            };
+            current = bin.path().to_path_buf();
+            chain.push(bin);
+        }
+        Ok(chain)
+    }
+
+    /// Checks that the interpreter this script runs with satisfies `req`, e.g.
+    /// `>=3.10` for a `#!/usr/bin/env python3` script.
+    ///
+    /// Only the script's own interpreter is verified, resolving `env` lines on
+    /// `$PATH` as [`ExecutableBin::verify_interpreter_chain`] does.
+    ///
+    /// # Returns
+    /// * The interpreter's version, if it satisfies `req`
+    pub fn verify_interpreter_satisfies(
+        &self,
+        req: &VersionReq,
+    ) -> Result<Version, InterpreterError> {
+        let line = self
+            .is_script()?
+            .ok_or_else(|| InterpreterError::NotAScript(self.path().into()))?;
+        let interpreter = ExecutableBin::new(interpreter_path(&line)?)?;
+        let version = interpreter.version()?;
+        if req.matches(&version) {
+            Ok(version)
+        } else {
+            Err(InterpreterError::Unsatisfied {
+                interpreter: interpreter.path().into(),
+                version,
+                required: req.clone(),
+            })
+        }
+    }
+
+    /// Where a `#!/usr/bin/env X` script's interpreter `X` is found on the current
+    /// `$PATH`.
+    ///
+    /// # Returns
+    /// * `None` for anything but an `env`-style script
+    /// * An [`io::ErrorKind::NotFound`] error if `X` isn't on `$PATH`
+    pub fn resolve_env_shebang(&self) -> io::Result<Option<PathBuf>> {
+        let Some(line) = self.is_script()? else {
+            return Ok(None);
+        };
+        let Some(name) = env_program(&line) else {
+            return Ok(None);
+        };
+        find_on_path(name).map(Some).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("interpreter {name} is not on PATH"),
+            )
+        })
+    }
+
+    /// The binary's size in bytes, following links.
+    pub fn size(&self) -> Option<u64> {
+        fs::metadata(self.path()).ok().map(|m| m.len())
+    }
+
+    /// The binary's size with a binary prefix and one decimal, e.g. `12.4 MiB`.
+    ///
+    /// Sizes under 1 KiB are given in plain bytes.
+    pub fn human_size(&self) -> Option<String> {
+        self.size().map(format_size)
+    }
+
+    /// Whether this binary and `other` have byte-identical contents.
+    ///
+    /// Files of different sizes are rejected without reading them; otherwise both
+    /// are streamed and compared chunk by chunk.
+    pub fn content_equals(&self, other: &ExecutableBin) -> io::Result<bool> {
+        if fs::metadata(self.path())?.len() != fs::metadata(other.path())?.len() {
+            return Ok(false);
+        }
+        let (mut ours, mut theirs) = (File::open(self.path())?, File::open(other.path())?);
+        let (mut our_chunk, mut their_chunk) = ([0; 8192], [0; 8192]);
+        loop {
+            let read = fill(&mut ours, &mut our_chunk)?;
+            if read != fill(&mut theirs, &mut their_chunk)?
+                || our_chunk[..read] != their_chunk[..read]
+            {
+                return Ok(false);
+            }
+            if read == 0 {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Reads the setuid, setgid and sticky bits of the binary's mode.
+    ///
+    /// Links are followed, so this describes the file that would actually run.
+    pub fn privilege_bits(&self) -> io::Result<PrivilegeBits> {
+        let mode = fs::metadata(self.path())?.permissions().mode();
+        Ok(PrivilegeBits {
+            setuid: mode & 0o4000 != 0,
+            setgid: mode & 0o2000 != 0,
+            sticky: mode & 0o1000 != 0,
+        })
+    }
+}
+
+/// The program a shebang line runs, looking through `env` to the program it starts.
+fn interpreter_path(line: &str) -> Result<PathBuf, ExecutableVerificationError> {
+    match env_program(line) {
+        Some(name) => find_on_path(name)
+            .ok_or_else(|| ExecutableVerificationError::NotOnPath(name.to_string())),
+        None => Ok(PathBuf::from(
+            line.split_whitespace().next().unwrap_or_default(),
+        )),
+    }
+}
+
+/// For an `env`-style shebang line, the name of the program `env` starts.
+fn env_program(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    let program = words.next()?;
+    if Path::new(program).file_name()? != "env" {
+        return None;
+    }
+    // Skip env's own flags (like -S) and VAR=value assignments
+    words.find(|word| !word.starts_with('-') && !word.contains('='))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, with_path_prefix, write_script};
+
+    /// An executable script at `path` run by the interpreter line `shebang`.
+    fn script_with(path: &Path, shebang: &str) -> ExecutableBin {
+        fs::write(path, format!("#!{shebang}\necho hello\n")).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        ExecutableBin::new_unverified(path.into(), String::new())
+    }
+
+    #[test]
+    fn privilege_bits_reads_setuid() {
+        let dir = TempDir::new();
+        let path = write_script(&dir.join("tool"), "echo tool 1.0.0");
+        let bin = ExecutableBin::new(&path).unwrap();
+        assert_eq!(bin.privilege_bits().unwrap(), PrivilegeBits::default());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o4755)).unwrap();
+        let bits = bin.privilege_bits().unwrap();
+        assert!(bits.setuid);
+        assert!(!bits.setgid && !bits.sticky);
+    }
+
+    #[test]
+    fn is_script_returns_the_interpreter_line_of_scripts_only() {
+        let dir = TempDir::new();
+        let script = dir.join("tool");
+        fs::write(&script, "#!/usr/bin/env python3 \nprint('tool 1.0.0')\n").unwrap();
+        let bin = ExecutableBin::from_parts(script, String::new(), None);
+        assert_eq!(
+            bin.is_script().unwrap().as_deref(),
+            Some("/usr/bin/env python3")
+        );
+
+        let native =
+            ExecutableBin::from_parts(std::env::current_exe().unwrap(), String::new(), None);
+        assert_eq!(native.is_script().unwrap(), None);
+    }
+
+    #[test]
+    fn format_size_rounds_across_unit_boundaries() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1_048_524), "1023.9 KiB");
+        assert_eq!(format_size(1_048_525), "1.0 MiB");
+        assert_eq!(format_size(13_002_342), "12.4 MiB");
+        assert_eq!(format_size(1_073_689_395), "1023.9 MiB");
+        assert_eq!(format_size(1_073_689_396), "1.0 GiB");
+        assert_eq!(format_size(5 << 40), "5120.0 GiB");
+    }
+
+    #[test]
+    fn content_equals_compares_every_byte() {
+        let dir = TempDir::new();
+        let contents = (0..20_000u32).map(|n| n as u8).collect::<Vec<_>>();
+        let mut differing = contents.clone();
+        *differing.last_mut().unwrap() ^= 1;
+        let file = |name: &str, contents: &[u8]| {
+            fs::write(dir.join(name), contents).unwrap();
+            ExecutableBin::new_unverified(dir.join(name), String::new())
+        };
+        let (original, copy) = (file("original", &contents), file("copy", &contents));
+        let (differing, shorter) = (
+            file("differing", &differing),
+            file("shorter", &contents[..19_999]),
+        );
+
+        assert!(original.content_equals(&copy).unwrap());
+        assert!(original.content_equals(&original).unwrap());
+        assert!(!original.content_equals(&differing).unwrap());
+        assert!(!original.content_equals(&shorter).unwrap());
+        let missing = ExecutableBin::new_unverified(dir.join("missing"), String::new());
+        assert!(original.content_equals(&missing).is_err());
+    }
+
+    #[test]
+    fn verify_interpreter_chain_follows_and_verifies_interpreters() {
+        let dir = TempDir::new();
+        let interpreter = write_script(&dir.join("interp"), "echo 'interp 1.0.0'");
+        let script = script_with(
+            &dir.join("present"),
+            &format!("{} -x", interpreter.display()),
+        );
+        let chain = script.verify_interpreter_chain().unwrap();
+        // The interpreter is itself a script, run by /bin/sh
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].path(), interpreter);
+        assert_eq!(chain[0].version_report(), "interp 1.0.0");
+        assert_eq!(chain[1].path(), Path::new("/bin/sh"));
+
+        let absent = script_with(&dir.join("absent"), &dir.join("gone").to_string_lossy());
+        assert!(matches!(
+            absent.verify_interpreter_chain(),
+            Err(ExecutableVerificationError::MissingPath(path)) if path == dir.join("gone")
+        ));
+        let via_env = script_with(
+            &dir.join("via-env"),
+            "/usr/bin/env -S symlistow-no-such-interp",
+        );
+        assert!(matches!(
+            via_env.verify_interpreter_chain(),
+            Err(ExecutableVerificationError::NotOnPath(name)) if name == "symlistow-no-such-interp"
+        ));
+        let binary = ExecutableBin::new_unverified("/bin/sh".into(), String::new());
+        assert!(binary.verify_interpreter_chain().unwrap().is_empty());
+    }
+
+    #[test]
+    fn identify_by_hash_looks_the_digest_up_in_the_db() {
+        let dir = TempDir::new();
+        fs::write(dir.join("known"), "abc").unwrap();
+        fs::write(dir.join("unknown"), "abd").unwrap();
+        let db = BTreeMap::from([(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            "tool 1.0.0".to_string(),
+        )]);
+
+        assert_eq!(
+            identify_by_hash(&dir.join("known"), &db)
+                .unwrap()
+                .as_deref(),
+            Some("tool 1.0.0")
+        );
+        assert_eq!(identify_by_hash(&dir.join("unknown"), &db).unwrap(), None);
+        assert!(identify_by_hash(&dir.join("missing"), &db).is_err());
+    }
+
+    #[test]
+    fn resolve_env_shebang_finds_the_interpreter_on_path() {
+        let dir = TempDir::new();
+        let bin = dir.join("bin");
+        fs::create_dir(&bin).unwrap();
+        let python = write_script(&bin.join("python3"), "echo 'Python 3.12.1'");
+        let script = script_with(&dir.join("tool.py"), "/usr/bin/env python3");
+        let missing = script_with(&dir.join("tool.rb"), "/usr/bin/env symlistow-no-such-ruby");
+        let direct = script_with(&dir.join("tool.sh"), "/bin/sh -e");
+
+        let (resolved, unresolved) = with_path_prefix(&[&bin], || {
+            (script.resolve_env_shebang(), missing.resolve_env_shebang())
+        });
+        assert_eq!(resolved.unwrap(), Some(python));
+        assert_eq!(unresolved.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(direct.resolve_env_shebang().unwrap(), None);
+        let binary = ExecutableBin::new_unverified(dir.join("data"), String::new());
+        fs::write(binary.path(), b"\x7fELF").unwrap();
+        assert_eq!(binary.resolve_env_shebang().unwrap(), None);
+    }
+
+    #[test]
+    fn toolchain_fingerprint_ignores_order_and_paths_but_not_versions() {
+        let bin =
+            |path: &str, report: &str| ExecutableBin::new_unverified(path.into(), report.into());
+        let toolchain = [
+            bin("/usr/bin/cc", "cc 13.2.0"),
+            bin("/usr/bin/ld", "GNU ld 2.42"),
+            bin("/usr/bin/make", "GNU Make 4.3"),
+        ];
+        let digest = toolchain_fingerprint(&toolchain);
+        assert_eq!(digest.len(), 64);
+
+        let shuffled = [
+            toolchain[2].clone(),
+            toolchain[0].clone(),
+            toolchain[1].clone(),
+        ];
+        assert_eq!(toolchain_fingerprint(&shuffled), digest);
+        let elsewhere = [
+            bin("/opt/gnu/bin/make", "GNU Make 4.3"),
+            bin("/opt/gnu/bin/cc", "cc 13.2.0"),
+            bin("/opt/gnu/bin/ld", "GNU ld 2.42"),
+        ];
+        assert_eq!(toolchain_fingerprint(&elsewhere), digest);
+
+        let upgraded = [
+            toolchain[0].clone(),
+            bin("/usr/bin/ld", "GNU ld 2.43"),
+            toolchain[2].clone(),
+        ];
+        assert_ne!(toolchain_fingerprint(&upgraded), digest);
+        assert_ne!(toolchain_fingerprint(&toolchain[..2]), digest);
+    }
+
+    #[test]
+    fn find_world_writable_flags_only_o_plus_w_executables() {
+        let dir = TempDir::new();
+        let open = write_script(&dir.join("open"), "echo open 1.0.0");
+        fs::set_permissions(&open, fs::Permissions::from_mode(0o777)).unwrap();
+        write_script(&dir.join("locked"), "echo locked 1.0.0");
+        let data = dir.join("data");
+        fs::write(&data, "not a program").unwrap();
+        fs::set_permissions(&data, fs::Permissions::from_mode(0o666)).unwrap();
+        std::os::unix::fs::symlink(&open, dir.join("alias")).unwrap();
+
+        let mut writable = find_world_writable(dir.path()).unwrap();
+        writable.sort();
+        assert_eq!(writable, [dir.join("alias"), open]);
+        assert!(find_world_writable(&dir.join("missing")).is_err());
+    }
+
+    #[test]
+    fn verify_interpreter_satisfies_checks_the_interpreter_version() {
+        let dir = TempDir::new();
+        let (old, new) = (dir.join("old"), dir.join("new"));
+        fs::create_dir(&old).unwrap();
+        fs::create_dir(&new).unwrap();
+        let old_python = write_script(&old.join("python3"), "echo 'Python 3.8.10'");
+        write_script(&new.join("python3"), "echo 'Python 3.12.1'");
+        let req = VersionReq::parse(">=3.10").unwrap();
+
+        let pinned = script_with(&dir.join("pinned.py"), &old_python.to_string_lossy());
+        match pinned.verify_interpreter_satisfies(&req) {
+            Err(InterpreterError::Unsatisfied {
+                interpreter,
+                version,
+                required,
+            }) => {
+                assert_eq!(interpreter, old_python);
+                assert_eq!(version, Version::new(3, 8, 10));
+                assert_eq!(required, req);
+            }
+            other => panic!("expected Unsatisfied, got {other:?}"),
+        }
+
+        let portable = script_with(&dir.join("portable.py"), "/usr/bin/env python3");
+        with_path_prefix(&[&new], || {
+            assert_eq!(
+                portable.verify_interpreter_satisfies(&req).unwrap(),
+                Version::new(3, 12, 1)
+            );
+        });
+
+        assert!(matches!(
+            ExecutableBin::new_unverified(old.join("missing"), String::new())
+                .verify_interpreter_satisfies(&req),
+            Err(InterpreterError::Read(_))
+        ));
+        fs::write(dir.join("plain"), "no shebang").unwrap();
+        assert!(matches!(
+            ExecutableBin::new_unverified(dir.join("plain"), String::new())
+                .verify_interpreter_satisfies(&req),
+            Err(InterpreterError::NotAScript(_))
+        ));
+    }
+}
@@ -0,0 +1,96 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, params};
+
+use crate::ExecutableBin;
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS inventory (
+    path BLOB PRIMARY KEY,
+    name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    verified_at INTEGER NOT NULL
+)";
+
+/// Records `bins` in the `inventory` table of the SQLite database at `db_path`.
+///
+/// The database and table are created if needed. Rows are keyed by path, so
+/// recording a binary again replaces its version and `verified_at`, the time of
+/// this call in seconds since the Unix epoch.
+pub fn record_inventory(db_path: &Path, bins: &[ExecutableBin]) -> rusqlite::Result<()> {
+    let mut conn = Connection::open(db_path)?;
+    conn.execute(CREATE_TABLE, [])?;
+    let verified_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+
+    let tx = conn.transaction()?;
+    {
+        let mut upsert = tx.prepare(
+            "INSERT INTO inventory (path, name, version, verified_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (path) DO UPDATE SET
+                 name = excluded.name,
+                 version = excluded.version,
+                 verified_at = excluded.verified_at",
+        )?;
+        for bin in bins {
+            upsert.execute(params![
+                bin.path().as_os_str().as_bytes(),
+                bin.name(),
+                bin.version_report(),
+                verified_at
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Reads back the binaries recorded by [`record_inventory`], ordered by path.
+///
+/// The binaries aren't re-verified; see [`ExecutableBin::new_unverified`].
+pub fn load_inventory(db_path: &Path) -> rusqlite::Result<Vec<ExecutableBin>> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(CREATE_TABLE, [])?;
+    let mut select = conn.prepare("SELECT path, version FROM inventory ORDER BY path")?;
+    select
+        .query_map([], |row| {
+            let path: Vec<u8> = row.get(0)?;
+            let path = PathBuf::from(OsStr::from_bytes(&path));
+            Ok(ExecutableBin::new_unverified(path, row.get(1)?))
+        })?
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn record_inventory_round_trips_and_upserts_by_path() {
+        let dir = TempDir::new();
+        let db = dir.join("inventory.db");
+        let bin =
+            |path: &str, report: &str| ExecutableBin::new_unverified(path.into(), report.into());
+        record_inventory(
+            &db,
+            &[
+                bin("/usr/bin/git", "git version 2.43.0"),
+                bin("/opt/cargo/bin/cargo", "cargo 1.75.0"),
+            ],
+        )
+        .unwrap();
+        record_inventory(&db, &[bin("/usr/bin/git", "git version 2.44.0")]).unwrap();
+
+        assert_eq!(
+            load_inventory(&db).unwrap(),
+            [
+                bin("/opt/cargo/bin/cargo", "cargo 1.75.0"),
+                bin("/usr/bin/git", "git version 2.44.0"),
+            ]
+        );
+        assert!(load_inventory(&dir.join("empty.db")).unwrap().is_empty());
+    }
+}
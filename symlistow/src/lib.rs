@@ -1,41 +1,423 @@
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::os::unix::fs as unix_fs;
+use std::os::unix::process::ExitStatusExt;
 use std::path::{Path, PathBuf};
-use std::process::{Command, ExitStatus};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Duration;
+
+mod analysis;
+mod batch;
+mod cache;
+mod elf;
+mod executable;
+mod family;
+mod inspect;
+#[cfg(feature = "sqlite")]
+mod inventory;
+mod license;
+mod link;
+mod link_state;
+mod linker;
+mod manifest;
+#[cfg(feature = "serde")]
+mod manifest_json;
+mod parallel;
+mod provenance;
+#[cfg(feature = "pty")]
+mod pty;
+mod report;
+#[cfg(target_os = "linux")]
+mod rlimit;
+mod runner;
+mod search;
+#[cfg(feature = "signatures")]
+mod signature;
+#[cfg(test)]
+mod test_util;
+mod version;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use analysis::{
+    CompatRule, CompatViolation, Outdated, Regression, assert_no_regression, check_compatibility,
+    find_outdated, group_by_inode, group_by_major, total_size,
+};
+pub use batch::{
+    BudgetedVerification, GlobError, verify_all_strict, verify_all_with_flags, verify_all_within,
+    verify_glob_parallel,
+};
+pub use cache::{VersionCache, verify_if_changed};
+pub use elf::Arch;
+pub use executable::{
+    ExecutableBin, HELP_TIMEOUT, SmokeTestError, verify_best_of, verify_binary_eventually,
+};
+pub use family::DEFAULT_FAMILIES;
+pub use inspect::{
+    InterpreterError, PrivilegeBits, find_world_writable, identify_by_hash, toolchain_fingerprint,
+};
+#[cfg(feature = "sqlite")]
+pub use inventory::{load_inventory, record_inventory};
+pub use link::{
+    LinkError, LinkOptions, PlanAction, PlanLine, ReplacePolicy, SymlinkError, SymlinkOutcome,
+    link_binary, link_binary_with, link_dir_into, link_dir_into_parallel, plan_diff,
+    verify_and_link,
+};
+pub use link_state::{
+    LinkAudit, LinkAuditStatus, LinkSnapshot, audit_links, describe_link, detect_symlink_cycle,
+    find_redundant_links, link_is_runnable, restore_links, set_current, snapshot_links,
+};
+pub use linker::{CopyLinker, HardlinkLinker, Linker, SymlinkLinker};
+pub use manifest::{
+    Reconciliation, VersionCheck, parse_tool_list, parse_tool_versions, reconcile,
+    verify_tool_list, verify_tool_versions,
+};
+#[cfg(feature = "serde")]
+pub use manifest_json::{ManifestCheck, apply_link_spec, verify_manifest};
+pub use provenance::Provenance;
+#[cfg(feature = "pty")]
+pub use pty::verify_binary_pty;
+pub use report::{
+    render_table, to_github_annotations, to_github_notices, to_install_script, to_markdown_catalog,
+    to_prometheus_textfile,
+};
+#[cfg(target_os = "linux")]
+pub use rlimit::ResourceLimits;
+pub use runner::{CommandRunner, SystemRunner, WasmRunner};
+pub use search::{
+    ShadowResolution, SkewReport, all_versions_on_path, detect_path_skew, find_basename_conflicts,
+    find_on_path, prune_path, scan_dir_filtered, scan_locations, scan_standard_locations,
+    shadow_resolution, standard_locations, verify_named, which_all, which_line,
+};
+pub use semver;
+#[cfg(feature = "signatures")]
+pub use signature::{SignatureError, SignaturePolicy};
+pub use version::{
+    DEFAULT_PLACEHOLDER_PATTERNS, VersionMismatch, VersionParseError, parse_version_report,
+};
+#[cfg(feature = "watch")]
+pub use watch::{WATCH_DEBOUNCE, watch_and_verify};
 
 /// Err Type for failed verification
 #[derive(Debug, thiserror::Error)]
-enum VerificationError {
+pub enum ExecutableVerificationError {
     #[error("Path doesn't exist: {0}")]
     MissingPath(PathBuf),
     #[error("Failed call to --version, got {0}")]
     VersionCallFail(ExitStatus),
     #[error("Binary did not execute successfully: {0}")]
     ExecutionError(#[from] io::Error),
+    #[error("No executable named {0} on PATH")]
+    NotOnPath(String),
+    #[error("Refusing to probe at nesting depth {0}, is a shim re-invoking itself?")]
+    ProbeDepthExceeded(usize),
+    #[error("Version call was stopped by its resource limits, got {0}")]
+    ResourceLimitExceeded(ExitStatus),
+    #[error("Not an executable ELF file or script: {0}")]
+    NotExecutable(PathBuf),
+    #[error("Version call wrote more than {0} bytes and was stopped")]
+    OutputTooLarge(u64),
+}
+
+/// Environment variable set for every version probe.
+///
+/// Its value is the probe nesting depth, starting at `1`. Shims and wrappers
+/// (pyenv, nvm, ...) can check it to answer `--version` directly instead of
+/// re-executing themselves, and a probe started from inside another probe
+/// increments it rather than resetting it.
+pub const PROBE_ENV_VAR: &str = "SYMLISTOW_PROBE";
+
+/// Deepest probe nesting allowed before verification gives up.
+pub const MAX_PROBE_DEPTH: usize = 4;
+
+/// The version report of a binary checked with [`VerifyOptions::no_exec`].
+pub const NO_EXEC_REPORT: &str = "<unverified: no-exec mode>";
+
+/// Options controlling how a binary's version is probed.
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// Arguments that make the binary print its version, `["--version"]` by default.
+    pub version_args: Vec<String>,
+    /// Run the version command with `PATH` limited to the binary's own directory,
+    /// `/usr/bin` and `/bin`, so sibling tools elsewhere on the caller's `PATH`
+    /// can't influence its output. By default the child inherits `PATH`.
+    pub minimal_path: bool,
+    /// Exit codes that count as a successful version call, `[0]` by default.
+    pub accepted_exit_codes: Vec<i32>,
+    /// Strip a leading UTF-8 byte order mark and turn CRLF line endings into LF,
+    /// as Windows tools often emit. On by default.
+    pub normalize_line_endings: bool,
+    /// Remove ANSI escape sequences, such as colors, from the version report. On
+    /// by default.
+    pub strip_ansi: bool,
+    /// Limits on the CPU time and memory of the version command, for probing
+    /// untrusted binaries. A probe stopped by them fails with
+    /// [`ExecutableVerificationError::ResourceLimitExceeded`]. Unlimited by default.
+    #[cfg(target_os = "linux")]
+    pub resource_limits: Option<ResourceLimits>,
+    /// Kill the version command if it runs longer than this, failing with an
+    /// [`io::ErrorKind::TimedOut`] error. No limit by default.
+    pub timeout: Option<Duration>,
+    /// Never run the binary, for sandboxes that forbid spawning processes. It
+    /// only has to exist, have an execute bit and start with an ELF or `#!`
+    /// header, failing with [`ExecutableVerificationError::NotExecutable`]
+    /// otherwise, and its report is [`NO_EXEC_REPORT`] with a zero exit status.
+    /// Off by default.
+    pub no_exec: bool,
+    /// Kill the version command once it writes more than this many bytes to
+    /// stdout, failing with [`ExecutableVerificationError::OutputTooLarge`], so a
+    /// broken or hostile binary can't exhaust memory. At most as much stderr is
+    /// kept. No limit by default.
+    pub max_output_bytes: Option<u64>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            version_args: vec!["--version".to_string()],
+            minimal_path: false,
+            accepted_exit_codes: vec![0],
+            normalize_line_endings: true,
+            strip_ansi: true,
+            #[cfg(target_os = "linux")]
+            resource_limits: None,
+            timeout: None,
+            no_exec: false,
+            max_output_bytes: None,
+        }
+    }
 }
 
 /// Verifies that a binary exists and can run --version.
 /// Returns Ok with the version string on success, or Err with an error message.
-fn verify_binary(binary_path: &str, binary_name: &str) -> Result<String, VerificationError> {
-    if !Path::new(binary_path).exists() {
-        return Err(VerificationError::MissingPath(binary_path.into()));
+pub(crate) fn verify_binary(binary_path: &Path) -> Result<String, ExecutableVerificationError> {
+    verify_binary_with(binary_path, &VerifyOptions::default())
+}
+
+/// Like [`verify_binary`], with explicit [`VerifyOptions`].
+pub(crate) fn verify_binary_with(
+    binary_path: &Path,
+    options: &VerifyOptions,
+) -> Result<String, ExecutableVerificationError> {
+    verify_output_via(binary_path, options, &SystemRunner).map(|output| output.version)
+}
+
+/// A successful version probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedOutput {
+    /// The trimmed stdout of the version command.
+    pub version: String,
+    /// How the version command exited, one of [`VerifyOptions::accepted_exit_codes`].
+    pub status: ExitStatus,
+    /// The stdout of the version command exactly as captured, before any cleanup.
+    pub raw_stdout: Vec<u8>,
+}
+
+/// Verifies that a binary runs its version command, keeping the exit status.
+///
+/// Useful with [`VerifyOptions::accepted_exit_codes`] for tools that exit with a
+/// meaningful non-zero code even when they print a valid version.
+pub fn verify_binary_output(
+    binary_path: &Path,
+    options: &VerifyOptions,
+) -> Result<VerifiedOutput, ExecutableVerificationError> {
+    verify_output_via(binary_path, options, &SystemRunner)
+}
+
+/// Runs the version command for `binary_path` through `runner`.
+///
+/// With the `tracing` feature each call runs in a `verify_binary` span recording
+/// the `path` and the `outcome`. With the `metrics` feature each call increments
+/// the `symlistow_verifications_total` counter, labelled with a `result` of
+/// `success` or `failure`, and records its duration in the
+/// `symlistow_verify_duration_seconds` histogram.
+pub(crate) fn verify_output_via(
+    binary_path: &Path,
+    options: &VerifyOptions,
+    runner: &dyn CommandRunner,
+) -> Result<VerifiedOutput, ExecutableVerificationError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::info_span!(
+        "verify_binary",
+        path = %binary_path.display(),
+        outcome = tracing::field::Empty,
+    )
+    .entered();
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+
+    let result = probe_version(binary_path, options, runner);
+
+    #[cfg(feature = "metrics")]
+    {
+        let label = if result.is_ok() { "success" } else { "failure" };
+        metrics::counter!("symlistow_verifications_total", "result" => label).increment(1);
+        metrics::histogram!("symlistow_verify_duration_seconds")
+            .record(started.elapsed().as_secs_f64());
     }
 
-    // Try to run the version command
-    let result = Command::new(binary_path).arg("--version").output();
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(_) => span.record("outcome", "verified"),
+        Err(e) => span.record("outcome", tracing::field::display(e)),
+    };
+    result
+}
 
-    match result {
-        Ok(output) => {
-            if output.status.success() {
-                let version = String::from_utf8_lossy(&output.stdout);
-                Ok(version.trim().to_string())
-            } else {
-                Err(VerificationError::VersionCallFail(output.status))
+/// Runs the version command for [`verify_output_via`].
+fn probe_version(
+    binary_path: &Path,
+    options: &VerifyOptions,
+    runner: &dyn CommandRunner,
+) -> Result<VerifiedOutput, ExecutableVerificationError> {
+    if options.no_exec {
+        return inspect_without_exec(binary_path);
+    }
+    let mut command = version_command(binary_path, options)?;
+    let output = match (options.max_output_bytes, options.timeout) {
+        (Some(max), timeout) => match runner.output_capped(&mut command, max, timeout) {
+            Err(e) if e.kind() == io::ErrorKind::FileTooLarge => {
+                return Err(ExecutableVerificationError::OutputTooLarge(max));
             }
+            output => output?,
+        },
+        (None, Some(timeout)) => runner.output_within(&mut command, timeout)?,
+        (None, None) => runner.output(&mut command)?,
+    };
+    let accepted = output
+        .status
+        .code()
+        .is_some_and(|code| options.accepted_exit_codes.contains(&code));
+    if accepted {
+        Ok(VerifiedOutput {
+            version: decode_report(&output.stdout, options),
+            status: output.status,
+            raw_stdout: output.stdout,
+        })
+    } else {
+        #[cfg(target_os = "linux")]
+        if rlimit::hit_limit(options.resource_limits, output.status) {
+            return Err(ExecutableVerificationError::ResourceLimitExceeded(
+                output.status,
+            ));
         }
-        Err(e) => Err(VerificationError::ExecutionError(e)),
+        Err(ExecutableVerificationError::VersionCallFail(output.status))
+    }
+}
+
+/// Checks from its metadata and header alone that `binary_path` is plausibly
+/// runnable, see [`VerifyOptions::no_exec`].
+fn inspect_without_exec(binary_path: &Path) -> Result<VerifiedOutput, ExecutableVerificationError> {
+    if !binary_path.exists() {
+        return Err(ExecutableVerificationError::MissingPath(binary_path.into()));
+    }
+    let mut magic = Vec::new();
+    fs::File::open(binary_path)?
+        .take(4)
+        .read_to_end(&mut magic)?;
+    let header = magic == b"\x7fELF" || magic.starts_with(b"#!");
+    if !(header && search::is_executable(binary_path)) {
+        return Err(ExecutableVerificationError::NotExecutable(
+            binary_path.into(),
+        ));
+    }
+    Ok(VerifiedOutput {
+        version: NO_EXEC_REPORT.to_string(),
+        status: ExitStatus::from_raw(0),
+        raw_stdout: Vec::new(),
+    })
+}
+
+/// Verifies that a binary runs `--version`, capturing stderr together with stdout.
+///
+/// Both streams of the child write into one pipe, so lines appear in the order
+/// the tool wrote them, as they would on a terminal.
+pub fn verify_binary_combined(binary_path: &Path) -> Result<String, ExecutableVerificationError> {
+    let mut command = version_command(binary_path, &VerifyOptions::default())?;
+    let (mut reader, writer) = io::pipe()?;
+    command
+        .stdin(Stdio::null())
+        .stdout(writer.try_clone()?)
+        .stderr(writer);
+    let mut child = command.spawn()?;
+    // The command holds copies of the write end; drop them so the read sees EOF
+    drop(command);
+
+    let mut combined = Vec::new();
+    reader.read_to_end(&mut combined)?;
+    let status = child.wait()?;
+    if status.success() {
+        Ok(decode_report(&combined, &VerifyOptions::default()))
+    } else {
+        Err(ExecutableVerificationError::VersionCallFail(status))
+    }
+}
+
+/// Decodes captured output as a trimmed, lossily decoded report, cleaned up as
+/// set by [`VerifyOptions::normalize_line_endings`] and [`VerifyOptions::strip_ansi`].
+fn decode_report(output: &[u8], options: &VerifyOptions) -> String {
+    let output = match output.strip_prefix(b"\xEF\xBB\xBF") {
+        Some(rest) if options.normalize_line_endings => rest,
+        _ => output,
+    };
+    let mut report = String::from_utf8_lossy(output).into_owned();
+    if options.normalize_line_endings {
+        report = report.replace("\r\n", "\n");
+    }
+    if options.strip_ansi {
+        report = version::strip_ansi(&report);
     }
+    report.trim().to_string()
+}
+
+/// The version command for `binary_path`, after checking it exists and that the
+/// probe isn't nested too deeply.
+fn version_command(
+    binary_path: &Path,
+    options: &VerifyOptions,
+) -> Result<Command, ExecutableVerificationError> {
+    if !binary_path.exists() {
+        return Err(ExecutableVerificationError::MissingPath(binary_path.into()));
+    }
+
+    let depth = probe_depth();
+    if depth >= MAX_PROBE_DEPTH {
+        return Err(ExecutableVerificationError::ProbeDepthExceeded(depth));
+    }
+
+    let mut command = Command::new(binary_path);
+    command
+        .args(&options.version_args)
+        .env(PROBE_ENV_VAR, (depth + 1).to_string());
+    if options.minimal_path {
+        command.env("PATH", minimal_path(binary_path));
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(limits) = options.resource_limits {
+        limits.apply(&mut command);
+    }
+    Ok(command)
+}
+
+/// The binary's own directory followed by the base system directories.
+fn minimal_path(binary_path: &Path) -> String {
+    let own_dir = binary_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| dir.to_string_lossy().into_owned());
+    own_dir
+        .into_iter()
+        .chain(["/usr/bin".to_string(), "/bin".to_string()])
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// The probe depth inherited from the environment, `0` outside of any probe.
+fn probe_depth() -> usize {
+    std::env::var(PROBE_ENV_VAR)
+        .map(|depth| depth.parse().unwrap_or(1))
+        .unwrap_or(0)
 }
 
 /// Prompts the user to decide whether to replace an existing binary.
@@ -85,10 +467,7 @@ pub fn handle_symlink(
 ) {
     if link_path.exists() {
         // Verify existing symlink/binary
-        match verify_binary(
-            link_path.to_str().unwrap(),
-            &format!("{} (existing)", binary_name),
-        ) {
+        match verify_binary(link_path) {
             Ok(existing_version) => {
                 if existing_version == source_version {
                     println!("✓ {} symlink already exists with same version", binary_name);
@@ -158,7 +537,7 @@ pub fn append_verified_binaries(path: &str, versions: &mut Vec<(String, String)>
         .and_then(|n| n.to_str())
         .unwrap_or(path);
 
-    match verify_binary(path, name) {
+    match verify_binary(Path::new(path)) {
         Ok(version) => {
             println!("✓ {}: {}", name, version);
             versions.push((path.to_string(), version));
@@ -170,3 +549,270 @@ pub fn append_verified_binaries(path: &str, versions: &mut Vec<(String, String)>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn probes_set_the_marker_a_shim_can_answer_from() {
+        let dir = TempDir::new();
+        // A shim that only answers when it can tell it is being probed
+        let shim = write_script(
+            &dir.join("python"),
+            &format!(
+                r#"[ -n "${PROBE_ENV_VAR}" ] && {{ echo "Python 3.12.1 (depth ${PROBE_ENV_VAR})"; exit 0; }}
+exit 1"#
+            ),
+        );
+        let version = verify_binary(&shim).unwrap();
+        assert_eq!(version, "Python 3.12.1 (depth 1)");
+    }
+
+    #[test]
+    fn minimal_path_limits_the_childs_path() {
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("tool"), r#"echo "$PATH""#);
+        let options = VerifyOptions {
+            minimal_path: true,
+            ..VerifyOptions::default()
+        };
+        let seen = verify_binary_with(&tool, &options).unwrap();
+        assert_eq!(seen, format!("{}:/usr/bin:/bin", dir.path().display()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn verifying_and_linking_record_spans_with_path_and_outcome() {
+        use crate::test_util::{RecordedSpan, SpanRecorder};
+
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("tool"), "echo tool 1.0.0");
+        let (missing, link) = (dir.join("missing"), dir.join("link"));
+        let recorder = SpanRecorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let bin = ExecutableBin::new(&tool).unwrap();
+            verify_binary(&missing).unwrap_err();
+            link_binary(&bin, &link, &LinkOptions::default()).unwrap();
+        });
+
+        let span = |name, fields: &[(&'static str, String)]| RecordedSpan {
+            name,
+            fields: fields.iter().cloned().collect(),
+        };
+        let path = |path: &Path| path.display().to_string();
+        assert_eq!(
+            recorder.spans(),
+            [
+                span(
+                    "verify_binary",
+                    &[("path", path(&tool)), ("outcome", "verified".into())]
+                ),
+                span(
+                    "verify_binary",
+                    &[
+                        ("path", path(&missing)),
+                        (
+                            "outcome",
+                            format!("Path doesn't exist: {}", missing.display())
+                        ),
+                    ]
+                ),
+                span(
+                    "create_symlink",
+                    &[
+                        ("path", path(&link)),
+                        ("source", path(&tool)),
+                        ("outcome", "Created".into()),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_binary_combined_keeps_stdout_and_stderr_in_order() {
+        let dir = TempDir::new();
+        let tool = write_script(
+            &dir.join("tool"),
+            "echo 'tool 1.0.0'\necho 'built with' >&2\necho 'cc 13.2'\necho 'on linux' >&2",
+        );
+        assert_eq!(
+            verify_binary_combined(&tool).unwrap(),
+            "tool 1.0.0\nbuilt with\ncc 13.2\non linux"
+        );
+        // The default keeps stdout only
+        assert_eq!(verify_binary(&tool).unwrap(), "tool 1.0.0\ncc 13.2");
+    }
+
+    #[test]
+    fn verify_binary_output_keeps_the_exit_status() {
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("tool"), "echo 'tool 1.0.0'");
+        let output = verify_binary_output(&tool, &VerifyOptions::default()).unwrap();
+        assert_eq!(output.version, "tool 1.0.0");
+        assert!(output.status.success());
+        assert_eq!(output.status.code(), Some(0));
+
+        let odd = write_script(&dir.join("odd"), "echo 'odd 2.0.0'\nexit 3");
+        let options = VerifyOptions {
+            accepted_exit_codes: vec![0, 3],
+            ..VerifyOptions::default()
+        };
+        let output = verify_binary_output(&odd, &options).unwrap();
+        assert_eq!(
+            (output.version.as_str(), output.status.code()),
+            ("odd 2.0.0", Some(3))
+        );
+    }
+
+    #[test]
+    fn windows_line_endings_and_bom_are_normalized_in_the_report_only() {
+        let raw = b"\xEF\xBB\xBFv1.0\r\n";
+        assert_eq!(decode_report(raw, &VerifyOptions::default()), "v1.0");
+
+        let dir = TempDir::new();
+        let tool = write_script(
+            &dir.join("tool.exe"),
+            r"printf '\357\273\277v1.0\r\nbuilt\r\n'",
+        );
+        let output = verify_binary_output(&tool, &VerifyOptions::default()).unwrap();
+        assert_eq!(output.version, "v1.0\nbuilt");
+        assert_eq!(output.raw_stdout, b"\xEF\xBB\xBFv1.0\r\nbuilt\r\n");
+
+        let options = VerifyOptions {
+            normalize_line_endings: false,
+            ..VerifyOptions::default()
+        };
+        assert_eq!(decode_report(raw, &options), "\u{feff}v1.0");
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn every_verification_increments_the_counter_for_its_result() {
+        use crate::test_util::CounterRecorder;
+
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("tool"), "echo tool 1.0.0");
+        let recorder = CounterRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            verify_binary(&tool).unwrap();
+            verify_binary(&tool).unwrap();
+            verify_binary(&dir.join("missing")).unwrap_err();
+        });
+        assert_eq!(
+            recorder.count("symlistow_verifications_total{result=success}"),
+            2
+        );
+        assert_eq!(
+            recorder.count("symlistow_verifications_total{result=failure}"),
+            1
+        );
+    }
+
+    #[test]
+    fn ansi_escapes_are_stripped_from_the_report_but_not_the_raw_bytes() {
+        let dir = TempDir::new();
+        let tool = write_script(
+            &dir.join("tool"),
+            r"printf '\033[1;32mtool\033[0m 1.0.0 (\033]8;;https://example.com\033\\docs\033]8;;\007)\n'",
+        );
+        let raw =
+            b"\x1b[1;32mtool\x1b[0m 1.0.0 (\x1b]8;;https://example.com\x1b\\docs\x1b]8;;\x07)\n";
+
+        let output = verify_binary_output(&tool, &VerifyOptions::default()).unwrap();
+        assert_eq!(output.version, "tool 1.0.0 (docs)");
+        assert_eq!(output.raw_stdout, raw);
+
+        let options = VerifyOptions {
+            strip_ansi: false,
+            ..VerifyOptions::default()
+        };
+        let output = verify_binary_output(&tool, &options).unwrap();
+        assert_eq!(output.version.as_bytes(), raw.trim_ascii_end());
+    }
+
+    /// Counts every command it is asked to run, and runs none.
+    #[derive(Default)]
+    struct CountingRunner(std::cell::Cell<usize>);
+
+    impl CommandRunner for CountingRunner {
+        fn output(&self, _: &mut Command) -> io::Result<std::process::Output> {
+            self.0.set(self.0.get() + 1);
+            Err(io::Error::other("nothing may run"))
+        }
+
+        fn output_within(
+            &self,
+            command: &mut Command,
+            _: Duration,
+        ) -> io::Result<std::process::Output> {
+            self.output(command)
+        }
+    }
+
+    #[test]
+    fn no_exec_checks_the_header_without_running_anything() {
+        let dir = TempDir::new();
+        let script = write_script(&dir.join("script"), "exit 1");
+        let elf = dir.join("elf");
+        fs::write(&elf, b"\x7fELF\x02\x01\x01").unwrap();
+        fs::set_permissions(&elf, fs::Permissions::from_mode(0o755)).unwrap();
+        let text = dir.join("text");
+        fs::write(&text, "echo not a program").unwrap();
+        fs::set_permissions(&text, fs::Permissions::from_mode(0o755)).unwrap();
+        let unmarked = dir.join("unmarked");
+        fs::write(&unmarked, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&unmarked, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let runner = CountingRunner::default();
+        let options = VerifyOptions {
+            no_exec: true,
+            timeout: Some(Duration::from_secs(1)),
+            ..VerifyOptions::default()
+        };
+        for path in [&script, &elf] {
+            let output = verify_output_via(path, &options, &runner).unwrap();
+            assert_eq!(output.version, NO_EXEC_REPORT);
+            assert!(output.status.success());
+            assert!(output.raw_stdout.is_empty());
+        }
+        for path in [&text, &unmarked] {
+            assert!(matches!(
+                verify_output_via(path, &options, &runner),
+                Err(ExecutableVerificationError::NotExecutable(p)) if p == *path
+            ));
+        }
+        assert!(matches!(
+            verify_output_via(&dir.join("missing"), &options, &runner),
+            Err(ExecutableVerificationError::MissingPath(_))
+        ));
+        assert_eq!(runner.0.get(), 0);
+    }
+
+    #[test]
+    fn max_output_bytes_stops_a_tool_that_never_stops_writing() {
+        let dir = TempDir::new();
+        let endless = write_script(&dir.join("endless"), "yes 'endless 1.0.0'");
+        let noisy = write_script(
+            &dir.join("noisy"),
+            "head -c 100000 /dev/zero >&2\necho 'noisy 1.0.0'",
+        );
+        for timeout in [None, Some(Duration::from_secs(10))] {
+            let options = VerifyOptions {
+                max_output_bytes: Some(4096),
+                timeout,
+                ..VerifyOptions::default()
+            };
+            assert!(matches!(
+                verify_binary_with(&endless, &options),
+                Err(ExecutableVerificationError::OutputTooLarge(4096))
+            ));
+            // Only stdout counts against the cap
+            assert_eq!(verify_binary_with(&noisy, &options).unwrap(), "noisy 1.0.0");
+        }
+    }
+}
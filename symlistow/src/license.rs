@@ -0,0 +1,100 @@
+use std::io;
+use std::process::Command;
+
+use crate::runner::output_with_timeout;
+use crate::{ExecutableBin, HELP_TIMEOUT};
+
+/// Phrases recognized as naming a license, and the SPDX identifier they mean.
+///
+/// Bare `GPL` and `BSD` don't say which version or variant, so they map to those
+/// family names rather than to an SPDX identifier.
+const LICENSE_PHRASES: &[(&str, &str)] = &[
+    ("AGPL-3.0", "AGPL-3.0"),
+    ("AGPLv3", "AGPL-3.0"),
+    ("LGPL-2.1", "LGPL-2.1"),
+    ("LGPLv2.1", "LGPL-2.1"),
+    ("LGPL-3.0", "LGPL-3.0"),
+    ("LGPLv3", "LGPL-3.0"),
+    ("GPL-2.0", "GPL-2.0"),
+    ("GPLv2", "GPL-2.0"),
+    ("GPL version 2", "GPL-2.0"),
+    ("GPL-3.0", "GPL-3.0"),
+    ("GPLv3", "GPL-3.0"),
+    ("GPL version 3", "GPL-3.0"),
+    ("GPL", "GPL"),
+    ("Apache-2.0", "Apache-2.0"),
+    ("Apache License 2.0", "Apache-2.0"),
+    ("Apache License, Version 2.0", "Apache-2.0"),
+    ("MPL-2.0", "MPL-2.0"),
+    ("BSD-2-Clause", "BSD-2-Clause"),
+    ("BSD-3-Clause", "BSD-3-Clause"),
+    ("BSD", "BSD"),
+    ("MIT", "MIT"),
+    ("ISC", "ISC"),
+];
+
+impl ExecutableBin {
+    /// Best-effort guess at the binary's license from what it prints.
+    ///
+    /// The output of `--license` is searched first, then the version report, for
+    /// common license names such as `MIT`, `Apache-2.0` or `GPLv3` as whole
+    /// words. The earliest phrase in the text wins, and the longest one where
+    /// several start at the same place.
+    /// `--license` runs under [`HELP_TIMEOUT`] and may fail; only errors running
+    /// it at all are returned.
+    pub fn detect_license(&self) -> io::Result<Option<String>> {
+        let output = output_with_timeout(Command::new(self.path()).arg("--license"), HELP_TIMEOUT)?;
+        let license_text = [output.stdout, output.stderr]
+            .map(|text| String::from_utf8_lossy(&text).into_owned())
+            .join("\n");
+        Ok(find_license(&license_text)
+            .or_else(|| find_license(self.version_report()))
+            .map(str::to_string))
+    }
+}
+
+/// The license named earliest in `text`.
+fn find_license(text: &str) -> Option<&'static str> {
+    LICENSE_PHRASES
+        .iter()
+        .filter_map(|(phrase, spdx)| Some((find_word(text, phrase)?, phrase.len(), *spdx)))
+        .min_by_key(|(position, len, _)| (*position, usize::MAX - len))
+        .map(|(_, _, spdx)| spdx)
+}
+
+/// The byte offset of the first occurrence of `phrase` in `text` not directly
+/// preceded or followed by a letter or digit.
+fn find_word(text: &str, phrase: &str) -> Option<usize> {
+    text.match_indices(phrase)
+        .map(|(start, _)| start)
+        .find(|&start| {
+            let before = text[..start].chars().next_back();
+            let after = text[start + phrase.len()..].chars().next();
+            !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn detect_license_falls_back_to_the_version_report() {
+        let dir = TempDir::new();
+        let tool = |name: &str, license: &str, version: &str| {
+            let script =
+                format!("case \"$1\" in\n--license) {license} ;;\n*) echo '{version}' ;;\nesac");
+            ExecutableBin::new(write_script(&dir.join(name), &script)).unwrap()
+        };
+
+        let mit = tool("mit", "exit 1", "mit 1.0.0, released under the MIT license");
+        assert_eq!(mit.detect_license().unwrap().as_deref(), Some("MIT"));
+
+        let gpl = tool("gpl", "echo 'Licensed under GPLv3'", "gpl 1.0.0 (MIT)");
+        assert_eq!(gpl.detect_license().unwrap().as_deref(), Some("GPL-3.0"));
+
+        let unknown = tool("unknown", "exit 1", "unknown 1.0.0 by MITRE");
+        assert_eq!(unknown.detect_license().unwrap(), None);
+    }
+}
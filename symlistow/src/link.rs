@@ -0,0 +1,1091 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::cache::FileStamp;
+use crate::inspect::sha256_file;
+use crate::link_state::snapshot_links;
+use crate::linker::{Linker, SymlinkLinker};
+use crate::parallel::map_bounded;
+use crate::search::is_executable;
+use crate::{ExecutableBin, ExecutableVerificationError, verify_binary};
+
+/// Err Type for failed link operations
+#[derive(Debug, thiserror::Error)]
+pub enum SymlinkError {
+    #[error("Failed to move existing {0} aside: {1}")]
+    BackupFailed(PathBuf, io::Error),
+    #[error("Failed to resolve link source {0}: {1}")]
+    ResolveFailed(PathBuf, io::Error),
+    #[error("Failed to create symlink {0}: {1}")]
+    CreateFailed(PathBuf, io::Error),
+    #[error("Link source {0} is outside every trusted root")]
+    SourceOutsideTrustedRoots(PathBuf),
+    #[error("Binary is in use by running processes: {pids:?}")]
+    BinaryInUse { pids: Vec<i32> },
+    #[error("Link {link} did not verify as its source: {reason}")]
+    LinkVerificationFailed { link: PathBuf, reason: String },
+    #[error("Refusing to replace existing {0}")]
+    Occupied(PathBuf),
+    #[error("Not enough free space to copy {needed} bytes, {available} available")]
+    InsufficientSpace { needed: u64, available: u64 },
+    #[error("Link source {0} changed between verification and linking")]
+    SourceChangedDuringOperation(PathBuf),
+    #[error("Linking would leave {would_be} links, more than the limit of {limit}")]
+    LinkLimitExceeded { limit: usize, would_be: usize },
+    #[error("Failed to read directory {0}: {1}")]
+    ReadDirFailed(PathBuf, io::Error),
+}
+
+/// Err Type for verify-then-link operations
+#[derive(Debug, thiserror::Error)]
+pub enum LinkError {
+    #[error(transparent)]
+    Verification(#[from] ExecutableVerificationError),
+    #[error(transparent)]
+    Symlink(#[from] SymlinkError),
+    #[cfg(feature = "serde")]
+    #[error("Failed to read link spec {0}: {1}")]
+    InvalidSpec(PathBuf, io::Error),
+}
+
+/// What a successful link operation did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkOutcome {
+    /// No link existed, a new one was created.
+    Created,
+    /// Something else was at the link path and has been replaced.
+    Replaced,
+    /// The link already pointed at the source.
+    Unchanged,
+    /// Something else was at the link path and was left in place, as asked by
+    /// [`ReplacePolicy::Keep`].
+    Kept,
+}
+
+/// What to do when the link path is already taken by something other than a
+/// link to the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplacePolicy {
+    /// Replace it, restoring it if linking fails.
+    #[default]
+    Replace,
+    /// Leave it in place and report [`SymlinkOutcome::Kept`].
+    Keep,
+    /// Fail with [`SymlinkError::Occupied`].
+    Fail,
+}
+
+/// Options controlling [`link_binary`].
+#[derive(Debug, Clone, Default)]
+pub struct LinkOptions {
+    /// Run the version command through the new link and require it to match the
+    /// source's version report. On mismatch the previous state is restored.
+    pub verify_link: bool,
+    /// Point the link at the source's fully resolved file instead of at the source
+    /// path as given. With a source `current -> v2/tool`, the link targets
+    /// `v2/tool` directly when set, and `current` (keeping that indirection) when not.
+    ///
+    /// Either way an existing link counts as already correct when it resolves to the
+    /// same final file as the source.
+    pub resolve_source: bool,
+    /// Refuse with [`SymlinkError::BinaryInUse`] when a running process is executing
+    /// the source or the file currently at the link path. Only checked on Linux,
+    /// where `/proc/*/exe` is scanned; processes of other users may not be visible.
+    pub check_not_running: bool,
+    /// Store the link target as a path relative to the link's directory, so the
+    /// whole tree can be moved without breaking links. An existing link is still
+    /// compared by the file it resolves to, whether its target is relative or not.
+    /// Ignored by backends that don't store a target path, such as hard links.
+    pub relative: bool,
+    /// Directories a source must resolve into to be linked, refused with
+    /// [`SymlinkError::SourceOutsideTrustedRoots`] otherwise. Empty allows any source.
+    pub trusted_roots: Vec<PathBuf>,
+    /// What to do with an existing entry at the link path, see [`ReplacePolicy`].
+    pub replace: ReplacePolicy,
+    /// Create missing parent directories of the link path. They are left in place
+    /// if linking then fails.
+    pub create_parents: bool,
+    /// The umask to create missing parent directories with, instead of the
+    /// process's. It is set only while [`LinkOptions::create_parents`] creates them
+    /// and then restored; as the umask is process-wide, other threads creating
+    /// files meanwhile see it too. Symlinks themselves ignore the umask.
+    pub umask: Option<u32>,
+    /// Guard against the source being swapped after it was verified: its size and
+    /// modification time must still be those seen at verification, and its
+    /// sha256 is recorded when linking starts and checked again right before the
+    /// link is created. Any difference fails with
+    /// [`SymlinkError::SourceChangedDuringOperation`], leaving the link path as
+    /// it was. This reads the whole source twice.
+    pub pin_checksum: bool,
+    /// The most managed links the target directory may hold after a bulk link
+    /// with [`link_dir_into`] or [`link_dir_into_parallel`], counting those
+    /// already there. A managed link is a symlink whose target is in the source
+    /// directory; other entries of the target directory don't count. Each source
+    /// counts unless a managed link of its name exists, whether or not it then
+    /// verifies. Over the limit nothing is linked. Single links ignore it.
+    /// Unlimited by default.
+    pub max_links: Option<usize>,
+}
+
+/// Creates or updates a symlink at `link_path` pointing at a verified binary.
+///
+/// Anything already at `link_path` is moved aside first and put back if creating
+/// or verifying the new link fails, so a failed call leaves the filesystem as it was.
+/// [`LinkOptions::replace`] can instead keep or refuse to touch an existing entry.
+///
+/// # Arguments
+/// * `source` - The verified binary the link should point at
+/// * `link_path` - The path where the symlink should be created
+/// * `options` - See [`LinkOptions`]
+pub fn link_binary(
+    source: &ExecutableBin,
+    link_path: &Path,
+    options: &LinkOptions,
+) -> Result<SymlinkOutcome, SymlinkError> {
+    link_binary_with(source, link_path, options, &SymlinkLinker)
+}
+
+/// Verifies `source` and, only if that succeeds, links it at `link_path`.
+///
+/// Nothing on disk is touched when verification fails.
+///
+/// # Returns
+/// * The verified source and what linking did
+pub fn verify_and_link(
+    source: &Path,
+    link_path: &Path,
+    policy: ReplacePolicy,
+) -> Result<(ExecutableBin, SymlinkOutcome), LinkError> {
+    let bin = ExecutableBin::new(source)?;
+    let options = LinkOptions {
+        replace: policy,
+        ..LinkOptions::default()
+    };
+    let outcome = link_binary(&bin, link_path, &options)?;
+    Ok((bin, outcome))
+}
+
+/// Like [`link_binary`], but installs the binary with any [`Linker`] backend.
+///
+/// With the `tracing` feature each call runs in a `create_symlink` span recording
+/// the link `path` and the `outcome`.
+pub fn link_binary_with(
+    source: &ExecutableBin,
+    link_path: &Path,
+    options: &LinkOptions,
+    linker: &dyn Linker,
+) -> Result<SymlinkOutcome, SymlinkError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::info_span!(
+        "create_symlink",
+        path = %link_path.display(),
+        source = %source.path().display(),
+        outcome = tracing::field::Empty,
+    )
+    .entered();
+
+    let result = create_link(source, link_path, options, linker);
+
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(outcome) => span.record("outcome", tracing::field::debug(outcome)),
+        Err(e) => span.record("outcome", tracing::field::display(e)),
+    };
+    result
+}
+
+/// The link flow behind [`link_binary_with`].
+fn create_link(
+    source: &ExecutableBin,
+    link_path: &Path,
+    options: &LinkOptions,
+    linker: &dyn Linker,
+) -> Result<SymlinkOutcome, SymlinkError> {
+    if !options.trusted_roots.is_empty() {
+        let resolved = fs::canonicalize(source.path())
+            .map_err(|e| SymlinkError::ResolveFailed(source.path().into(), e))?;
+        let trusted = options.trusted_roots.iter().any(|root| {
+            let root = fs::canonicalize(root).unwrap_or_else(|_| root.clone());
+            resolved.starts_with(root)
+        });
+        if !trusted {
+            return Err(SymlinkError::SourceOutsideTrustedRoots(resolved));
+        }
+    }
+
+    if options.check_not_running {
+        let in_use = [source.path(), link_path]
+            .iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .collect::<Vec<_>>();
+        let pids = running_pids(&in_use);
+        if !pids.is_empty() {
+            return Err(SymlinkError::BinaryInUse { pids });
+        }
+    }
+
+    // Checked ahead of the early returns so a link already pointing at a refused
+    // source isn't reported as fine
+    let occupied = fs::symlink_metadata(link_path).is_ok();
+    if occupied && linker.is_current(source.path(), link_path).unwrap_or(false) {
+        return Ok(SymlinkOutcome::Unchanged);
+    }
+    if occupied {
+        match options.replace {
+            ReplacePolicy::Replace => {}
+            ReplacePolicy::Keep => return Ok(SymlinkOutcome::Kept),
+            ReplacePolicy::Fail => return Err(SymlinkError::Occupied(link_path.into())),
+        }
+    }
+
+    let pinned = if options.pin_checksum {
+        Some(pin_source(source)?)
+    } else {
+        None
+    };
+
+    if options.create_parents
+        && let Some(parent) = link_path.parent().filter(|dir| !dir.as_os_str().is_empty())
+    {
+        let _umask = UmaskGuard::set(options.umask);
+        fs::create_dir_all(parent).map_err(|e| SymlinkError::CreateFailed(parent.into(), e))?;
+    }
+
+    let target = if options.resolve_source {
+        fs::canonicalize(source.path())
+            .map_err(|e| SymlinkError::ResolveFailed(source.path().into(), e))?
+    } else {
+        source.path().to_path_buf()
+    };
+
+    let target = if options.relative && linker.stores_target_path() {
+        relative_target(&target, link_path)
+            .map_err(|e| SymlinkError::ResolveFailed(source.path().into(), e))?
+    } else {
+        target
+    };
+
+    let backup = occupied.then(|| backup_path(link_path));
+    if let Some(backup) = &backup {
+        fs::rename(link_path, backup)
+            .map_err(|e| SymlinkError::BackupFailed(link_path.into(), e))?;
+    }
+
+    #[cfg(test)]
+    if let Some(hook) = AFTER_PIN.take() {
+        hook();
+    }
+    let result = match &pinned {
+        Some(pinned) => check_pin(source, pinned),
+        None => Ok(()),
+    };
+    let result = result
+        .and_then(|()| linker.link(&target, link_path))
+        .and_then(|()| {
+            if options.verify_link {
+                verify_link(source, link_path)
+            } else {
+                Ok(())
+            }
+        });
+
+    match result {
+        Ok(()) => {
+            if let Some(backup) = &backup {
+                let _ = fs::remove_file(backup);
+                Ok(SymlinkOutcome::Replaced)
+            } else {
+                Ok(SymlinkOutcome::Created)
+            }
+        }
+        Err(e) => {
+            rollback(link_path, backup.as_deref());
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Run once by [`create_link`] on this thread between pinning the source and
+    /// checking the pin, so tests can change the source inside that window.
+    static AFTER_PIN: std::cell::Cell<Option<Box<dyn FnOnce()>>> =
+        const { std::cell::Cell::new(None) };
+}
+
+/// The sha256 of `source`, after checking that it looks unchanged since it was
+/// verified.
+fn pin_source(source: &ExecutableBin) -> Result<String, SymlinkError> {
+    let path = source.path();
+    let stamp = FileStamp::of(path).map_err(|e| SymlinkError::ResolveFailed(path.into(), e))?;
+    if source.stamp().is_some_and(|verified| verified != stamp) {
+        return Err(SymlinkError::SourceChangedDuringOperation(path.into()));
+    }
+    sha256_file(path).map_err(|e| SymlinkError::ResolveFailed(path.into(), e))
+}
+
+/// Checks that `source` still has the sha256 recorded by [`pin_source`].
+fn check_pin(source: &ExecutableBin, pinned: &str) -> Result<(), SymlinkError> {
+    let path = source.path();
+    let now = sha256_file(path).map_err(|e| SymlinkError::ResolveFailed(path.into(), e))?;
+    if now == pinned {
+        Ok(())
+    } else {
+        Err(SymlinkError::SourceChangedDuringOperation(path.into()))
+    }
+}
+
+/// What applying a plan entry would do to its link path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanAction {
+    Create,
+    Replace,
+    Keep,
+}
+
+/// One entry of a [`plan_diff`]: a link's current state and the proposed change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanLine {
+    pub link: PathBuf,
+    /// The raw target of the symlink currently at `link`, if it is one.
+    pub current_target: Option<PathBuf>,
+    /// The version reported through `link` today, if it verifies.
+    pub current_version: Option<String>,
+    pub proposed_target: PathBuf,
+    pub proposed_version: String,
+    pub action: PlanAction,
+}
+
+impl fmt::Display for PlanLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let current = || {
+            format!(
+                "{} ({})",
+                self.current_target
+                    .as_ref()
+                    .map_or_else(|| "<not a symlink>".into(), |t| t.display().to_string()),
+                self.current_version.as_deref().unwrap_or("unverified")
+            )
+        };
+        let proposed = format!(
+            "{} ({})",
+            self.proposed_target.display(),
+            self.proposed_version
+        );
+        match self.action {
+            PlanAction::Create => write!(f, "create  {} -> {}", self.link.display(), proposed),
+            PlanAction::Replace => write!(
+                f,
+                "replace {}: {} -> {}",
+                self.link.display(),
+                current(),
+                proposed
+            ),
+            PlanAction::Keep => write!(f, "keep    {} -> {}", self.link.display(), current()),
+        }
+    }
+}
+
+/// Describes what [`link_binary`] would do for each `(link path, source)` pair,
+/// without touching the filesystem.
+pub fn plan_diff(plan: &[(PathBuf, ExecutableBin)]) -> Vec<PlanLine> {
+    plan.iter()
+        .map(|(link, source)| {
+            let occupied = fs::symlink_metadata(link).is_ok();
+            let action = if !occupied {
+                PlanAction::Create
+            } else if SymlinkLinker
+                .is_current(source.path(), link)
+                .unwrap_or(false)
+            {
+                PlanAction::Keep
+            } else {
+                PlanAction::Replace
+            };
+            PlanLine {
+                link: link.clone(),
+                current_target: SymlinkLinker.current_target(link).ok().flatten(),
+                current_version: occupied.then(|| verify_binary(link).ok()).flatten(),
+                proposed_target: source.path().into(),
+                proposed_version: source.version_report().into(),
+                action,
+            }
+        })
+        .collect()
+}
+
+/// Each source of a bulk link paired with what linking it did.
+type DirLinkOutcomes = Vec<(PathBuf, Result<SymlinkOutcome, LinkError>)>;
+
+/// Verifies every executable in `source_dir` and links it into `target_dir` under
+/// the same name.
+///
+/// # Returns
+/// * The outcome for each executable, sorted by source path
+///
+/// # Errors
+/// * [`SymlinkError::ReadDirFailed`] if `source_dir` can't be listed, or
+///   `target_dir` exists but can't be when [`LinkOptions::max_links`] is set
+/// * [`SymlinkError::LinkLimitExceeded`] if linking would take `target_dir` past
+///   [`LinkOptions::max_links`]
+pub fn link_dir_into(
+    source_dir: &Path,
+    target_dir: &Path,
+    options: &LinkOptions,
+) -> Result<DirLinkOutcomes, SymlinkError> {
+    link_dir_into_parallel(source_dir, target_dir, options, 1)
+}
+
+/// Like [`link_dir_into`], but verifies up to `max_threads` sources concurrently.
+///
+/// Only verification runs in parallel; links are created one at a time so
+/// concurrent replacements in `target_dir` can't race each other.
+pub fn link_dir_into_parallel(
+    source_dir: &Path,
+    target_dir: &Path,
+    options: &LinkOptions,
+    max_threads: usize,
+) -> Result<DirLinkOutcomes, SymlinkError> {
+    let read_dir_failed = |e| SymlinkError::ReadDirFailed(source_dir.into(), e);
+    let mut sources = Vec::new();
+    for entry in fs::read_dir(source_dir).map_err(read_dir_failed)? {
+        let path = entry.map_err(read_dir_failed)?.path();
+        if is_executable(&path) {
+            sources.push(path);
+        }
+    }
+    sources.sort();
+
+    if let Some(limit) = options.max_links {
+        let existing = match snapshot_links(target_dir) {
+            Ok(snapshot) => snapshot.links,
+            // Nothing is linked into a directory that isn't there yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Default::default(),
+            Err(e) => return Err(SymlinkError::ReadDirFailed(target_dir.into(), e)),
+        };
+        let source_root = fs::canonicalize(source_dir).map_err(read_dir_failed)?;
+        let managed = existing
+            .into_iter()
+            .filter(|(_, link_target)| {
+                // Relative targets are relative to the link's directory
+                let link_target = target_dir.join(link_target);
+                link_target
+                    .parent()
+                    .and_then(|dir| fs::canonicalize(dir).ok())
+                    .is_some_and(|dir| dir == source_root)
+            })
+            .map(|(name, _)| name)
+            .collect::<HashSet<_>>();
+        let added = sources
+            .iter()
+            .filter(|source| !managed.contains(source.file_name().unwrap_or_default()))
+            .count();
+        let would_be = managed.len() + added;
+        if would_be > limit {
+            return Err(SymlinkError::LinkLimitExceeded { limit, would_be });
+        }
+    }
+
+    let target_lock = Mutex::new(());
+    let outcomes = map_bounded(&sources, max_threads, |source| {
+        let bin = ExecutableBin::new(source)?;
+        let link_path = target_dir.join(source.file_name().unwrap_or_default());
+        let _guard = target_lock.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(link_binary(&bin, &link_path, options)?)
+    });
+    Ok(sources.into_iter().zip(outcomes).collect())
+}
+
+/// The ids of processes whose executable is one of `executables`.
+#[cfg(target_os = "linux")]
+fn running_pids(executables: &[PathBuf]) -> Vec<i32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut pids: Vec<i32> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let exe = fs::read_link(entry.path().join("exe")).ok()?;
+            executables.contains(&exe).then_some(pid)
+        })
+        .collect();
+    pids.sort_unstable();
+    pids
+}
+
+#[cfg(not(target_os = "linux"))]
+fn running_pids(_executables: &[PathBuf]) -> Vec<i32> {
+    Vec::new()
+}
+
+/// Checks that running the version command through `link_path` reports the same
+/// version as `source`.
+fn verify_link(source: &ExecutableBin, link_path: &Path) -> Result<(), SymlinkError> {
+    match verify_binary(link_path) {
+        Ok(version) if version == source.version_report() => Ok(()),
+        Ok(version) => Err(SymlinkError::LinkVerificationFailed {
+            link: link_path.into(),
+            reason: format!(
+                "expected version {:?}, got {:?}",
+                source.version_report(),
+                version
+            ),
+        }),
+        Err(e) => Err(SymlinkError::LinkVerificationFailed {
+            link: link_path.into(),
+            reason: e.to_string(),
+        }),
+    }
+}
+
+/// The path of `target` relative to the real directory containing `link_path`.
+///
+/// Directories on both sides are resolved first, since a relative link target is
+/// interpreted from the directory the link physically lives in.
+fn relative_target(target: &Path, link_path: &Path) -> io::Result<PathBuf> {
+    let resolve_dir = |path: &Path| -> io::Result<PathBuf> {
+        match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => fs::canonicalize(dir),
+            None => std::env::current_dir(),
+        }
+    };
+    let file_name = target.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "link source has no file name")
+    })?;
+    let target = resolve_dir(target)?.join(file_name);
+    let link_dir = resolve_dir(link_path)?;
+
+    let mut target_components = target.components().peekable();
+    let mut link_components = link_dir.components().peekable();
+    while let (Some(a), Some(b)) = (target_components.peek(), link_components.peek()) {
+        if a != b {
+            break;
+        }
+        target_components.next();
+        link_components.next();
+    }
+    let mut relative: PathBuf = link_components.map(|_| Component::ParentDir).collect();
+    relative.extend(target_components);
+    Ok(relative)
+}
+
+/// Removes whatever was created at `link_path` and puts the backup back, if any.
+fn rollback(link_path: &Path, backup: Option<&Path>) {
+    if fs::symlink_metadata(link_path).is_ok() {
+        let _ = fs::remove_file(link_path);
+    }
+    if let Some(backup) = backup {
+        let _ = fs::rename(backup, link_path);
+    }
+}
+
+/// Serializes umask changes, so overlapping guards restore the process's mask.
+static UMASK_LOCK: Mutex<()> = Mutex::new(());
+
+/// Replaces the process umask until dropped.
+///
+/// Guards hold [`UMASK_LOCK`] while set, so two linking threads can't each
+/// restore the other's mask.
+struct UmaskGuard(Option<(libc::mode_t, MutexGuard<'static, ()>)>);
+
+impl UmaskGuard {
+    fn set(mask: Option<u32>) -> Self {
+        Self(mask.map(|mask| {
+            let lock = UMASK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            // SAFETY: umask only swaps the process's file mode creation mask
+            (unsafe { libc::umask(mask as libc::mode_t) }, lock)
+        }))
+    }
+}
+
+impl Drop for UmaskGuard {
+    fn drop(&mut self) {
+        if let Some((previous, _)) = &self.0 {
+            // SAFETY: as in `UmaskGuard::set`
+            unsafe { libc::umask(*previous) };
+        }
+    }
+}
+
+/// The path an existing entry is moved to while it is being replaced.
+///
+/// The name is unique to this process and not yet taken, so moving the entry
+/// there can't overwrite anything else.
+fn backup_path(link_path: &Path) -> PathBuf {
+    let name = link_path
+        .file_name()
+        .map(OsString::from)
+        .unwrap_or_default();
+    let mut attempt = 0;
+    loop {
+        let mut candidate = name.clone();
+        candidate.push(format!(
+            ".symlistow-backup-{}-{attempt}",
+            std::process::id()
+        ));
+        let candidate = link_path.with_file_name(candidate);
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs as unix_fs;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    /// A script at `path` reporting `report` for `--version`.
+    fn tool(path: &Path, report: &str) -> ExecutableBin {
+        ExecutableBin::new(write_script(path, &format!("echo '{report}'"))).unwrap()
+    }
+
+    #[test]
+    fn verify_link_rejects_a_link_reporting_another_version_and_restores_the_old_one() {
+        let dir = TempDir::new();
+        let source = tool(&dir.join("tool-2"), "tool 2.0.0");
+        let old = tool(&dir.join("tool-1"), "tool 1.0.0");
+        let link = dir.join("tool");
+        unix_fs::symlink(old.path(), &link).unwrap();
+        // Rebuilt after it was verified, so the new link reports something else
+        write_script(source.path(), "echo 'tool 2.0.1'");
+
+        let options = LinkOptions {
+            verify_link: true,
+            ..LinkOptions::default()
+        };
+        let error = link_binary(&source, &link, &options).unwrap_err();
+        assert!(
+            matches!(&error, SymlinkError::LinkVerificationFailed { link: failed, .. } if *failed == link),
+            "{error}"
+        );
+        assert_eq!(fs::read_link(&link).unwrap(), old.path());
+        let mut names = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["tool", "tool-1", "tool-2"]);
+
+        let source = ExecutableBin::new(source.path()).unwrap();
+        let outcome = link_binary(&source, &link, &options).unwrap();
+        assert_eq!(outcome, SymlinkOutcome::Replaced);
+        assert_eq!(fs::read_link(&link).unwrap(), source.path());
+    }
+
+    #[test]
+    fn replacing_leaves_files_named_like_a_backup_alone() {
+        let dir = TempDir::new();
+        let source = tool(&dir.join("tool-2"), "tool 2.0.0");
+        let link = dir.join("tool");
+        fs::write(&link, "old").unwrap();
+        let unrelated = dir.join("tool.symlistow-backup");
+        fs::write(&unrelated, "keep").unwrap();
+
+        let outcome = link_binary(&source, &link, &LinkOptions::default()).unwrap();
+        assert_eq!(outcome, SymlinkOutcome::Replaced);
+        assert_eq!(fs::read_to_string(&unrelated).unwrap(), "keep");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn resolve_source_links_past_the_source_chain() {
+        let dir = TempDir::new();
+        let real = tool(&dir.join("tool-2"), "tool 2.0.0");
+        unix_fs::symlink(real.path(), dir.join("stable")).unwrap();
+        unix_fs::symlink(dir.join("stable"), dir.join("current")).unwrap();
+        let source = ExecutableBin::new(dir.join("current")).unwrap();
+
+        let as_given = dir.join("as-given");
+        link_binary(&source, &as_given, &LinkOptions::default()).unwrap();
+        assert_eq!(fs::read_link(&as_given).unwrap(), dir.join("current"));
+
+        let resolved = dir.join("resolved");
+        let options = LinkOptions {
+            resolve_source: true,
+            ..LinkOptions::default()
+        };
+        link_binary(&source, &resolved, &options).unwrap();
+        assert_eq!(fs::read_link(&resolved).unwrap(), real.path());
+        // Both resolve to the same file, so neither needs relinking
+        assert_eq!(
+            link_binary(&source, &as_given, &options).unwrap(),
+            SymlinkOutcome::Unchanged
+        );
+    }
+
+    #[test]
+    fn link_dir_into_parallel_links_every_executable_once() {
+        let dir = TempDir::new();
+        let (sources, target) = (dir.join("src"), dir.join("bin"));
+        fs::create_dir(&sources).unwrap();
+        fs::create_dir(&target).unwrap();
+        for n in 0..16 {
+            tool(
+                &sources.join(format!("tool{n:02}")),
+                &format!("tool{n:02} 1.0.{n}"),
+            );
+        }
+        fs::write(sources.join("README"), "not a tool").unwrap();
+
+        let outcomes =
+            link_dir_into_parallel(&sources, &target, &LinkOptions::default(), 4).unwrap();
+        assert_eq!(outcomes.len(), 16);
+        for (n, (source, outcome)) in outcomes.iter().enumerate() {
+            assert_eq!(*source, sources.join(format!("tool{n:02}")));
+            assert_eq!(*outcome.as_ref().unwrap(), SymlinkOutcome::Created);
+            let link = target.join(source.file_name().unwrap());
+            assert_eq!(fs::read_link(&link).unwrap(), *source);
+            assert_eq!(verify_binary(&link).unwrap(), format!("tool{n:02} 1.0.{n}"));
+        }
+        assert_eq!(fs::read_dir(&target).unwrap().count(), 16);
+
+        let again = link_dir_into_parallel(&sources, &target, &LinkOptions::default(), 4).unwrap();
+        assert!(
+            again
+                .iter()
+                .all(|(_, outcome)| matches!(outcome, Ok(SymlinkOutcome::Unchanged)))
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn check_not_running_refuses_a_binary_that_is_executing() {
+        let dir = TempDir::new();
+        let sleep = dir.join("sleep");
+        fs::copy(crate::find_on_path("sleep").unwrap(), &sleep).unwrap();
+        let source = ExecutableBin::new(&sleep).unwrap();
+        let mut running = std::process::Command::new(&sleep)
+            .arg("30")
+            .spawn()
+            .unwrap();
+
+        let options = LinkOptions {
+            check_not_running: true,
+            ..LinkOptions::default()
+        };
+        let link = dir.join("link");
+        let result = link_binary(&source, &link, &options);
+        // A link already pointing at the running binary is refused too
+        let current = dir.join("current");
+        unix_fs::symlink(&sleep, &current).unwrap();
+        let current_result = link_binary(&source, &current, &options);
+        running.kill().unwrap();
+        running.wait().unwrap();
+
+        for result in [result, current_result] {
+            match result {
+                Err(SymlinkError::BinaryInUse { pids }) => {
+                    assert!(pids.contains(&(running.id() as i32)), "{pids:?}");
+                }
+                other => panic!("expected BinaryInUse, got {other:?}"),
+            }
+        }
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(
+            link_binary(&source, &link, &options).unwrap(),
+            SymlinkOutcome::Created
+        );
+    }
+
+    #[test]
+    fn relative_links_store_a_relative_target_that_resolves_to_the_source() {
+        let dir = TempDir::new();
+        fs::create_dir(dir.join("opt")).unwrap();
+        fs::create_dir(dir.join("bin")).unwrap();
+        let source = tool(&dir.join("opt/tool-1"), "tool 1.0.0");
+        let link = dir.join("bin/tool");
+        let options = LinkOptions {
+            relative: true,
+            ..LinkOptions::default()
+        };
+
+        assert_eq!(
+            link_binary(&source, &link, &options).unwrap(),
+            SymlinkOutcome::Created
+        );
+        let raw = fs::read_link(&link).unwrap();
+        assert!(raw.is_relative(), "{}", raw.display());
+        assert_eq!(raw, Path::new("../opt/tool-1"));
+        assert_eq!(fs::canonicalize(&link).unwrap(), source.path());
+        assert_eq!(verify_binary(&link).unwrap(), "tool 1.0.0");
+
+        assert_eq!(
+            link_binary(&source, &link, &options).unwrap(),
+            SymlinkOutcome::Unchanged
+        );
+        // An absolute link to the same file counts as correct too
+        fs::remove_file(&link).unwrap();
+        unix_fs::symlink(source.path(), &link).unwrap();
+        assert_eq!(
+            link_binary(&source, &link, &options).unwrap(),
+            SymlinkOutcome::Unchanged
+        );
+    }
+
+    #[test]
+    fn plan_diff_describes_create_replace_and_keep() {
+        let dir = TempDir::new();
+        let old = tool(&dir.join("tool-1"), "tool 1.0.0");
+        let new = tool(&dir.join("tool-2"), "tool 2.0.0");
+        let (fresh, stale, current) = (dir.join("fresh"), dir.join("stale"), dir.join("current"));
+        unix_fs::symlink(old.path(), &stale).unwrap();
+        unix_fs::symlink(new.path(), &current).unwrap();
+        let plan = [
+            (fresh.clone(), new.clone()),
+            (stale.clone(), new.clone()),
+            (current.clone(), new.clone()),
+        ];
+
+        let lines = plan_diff(&plan);
+        assert_eq!(
+            lines,
+            [
+                PlanLine {
+                    link: fresh.clone(),
+                    current_target: None,
+                    current_version: None,
+                    proposed_target: new.path().into(),
+                    proposed_version: "tool 2.0.0".into(),
+                    action: PlanAction::Create,
+                },
+                PlanLine {
+                    link: stale.clone(),
+                    current_target: Some(old.path().into()),
+                    current_version: Some("tool 1.0.0".into()),
+                    proposed_target: new.path().into(),
+                    proposed_version: "tool 2.0.0".into(),
+                    action: PlanAction::Replace,
+                },
+                PlanLine {
+                    link: current.clone(),
+                    current_target: Some(new.path().into()),
+                    current_version: Some("tool 2.0.0".into()),
+                    proposed_target: new.path().into(),
+                    proposed_version: "tool 2.0.0".into(),
+                    action: PlanAction::Keep,
+                },
+            ]
+        );
+        assert_eq!(
+            lines[1].to_string(),
+            format!(
+                "replace {}: {} (tool 1.0.0) -> {} (tool 2.0.0)",
+                stale.display(),
+                old.path().display(),
+                new.path().display()
+            )
+        );
+        // Planning leaves the filesystem alone
+        assert!(fs::symlink_metadata(&fresh).is_err());
+        assert_eq!(fs::read_link(&stale).unwrap(), old.path());
+    }
+
+    #[test]
+    fn trusted_roots_refuse_sources_outside_them() {
+        let dir = TempDir::new();
+        fs::create_dir(dir.join("trusted")).unwrap();
+        fs::create_dir(dir.join("planted")).unwrap();
+        let inside = tool(&dir.join("trusted/tool"), "tool 1.0.0");
+        let outside = tool(&dir.join("planted/tool"), "tool 6.6.6");
+        let options = LinkOptions {
+            trusted_roots: vec![dir.join("trusted")],
+            ..LinkOptions::default()
+        };
+
+        let link = dir.join("tool");
+        match link_binary(&outside, &link, &options) {
+            Err(SymlinkError::SourceOutsideTrustedRoots(path)) => assert_eq!(path, outside.path()),
+            other => panic!("expected SourceOutsideTrustedRoots, got {other:?}"),
+        }
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(
+            link_binary(&inside, &link, &options).unwrap(),
+            SymlinkOutcome::Created
+        );
+        // A link inside the trusted root resolving out of it is still refused
+        unix_fs::symlink(outside.path(), dir.join("trusted/sneaky")).unwrap();
+        let sneaky = ExecutableBin::new(dir.join("trusted/sneaky")).unwrap();
+        assert!(matches!(
+            link_binary(&sneaky, &dir.join("other"), &options),
+            Err(SymlinkError::SourceOutsideTrustedRoots(_))
+        ));
+        // No roots allow any source
+        assert_eq!(
+            link_binary(&outside, &dir.join("other"), &LinkOptions::default()).unwrap(),
+            SymlinkOutcome::Created
+        );
+        // Nor is a link that already points at an untrusted source left as unchanged
+        assert!(matches!(
+            link_binary(&outside, &dir.join("other"), &options),
+            Err(SymlinkError::SourceOutsideTrustedRoots(_))
+        ));
+        // Nor does keeping an occupied link skip the check
+        let keep = LinkOptions {
+            replace: ReplacePolicy::Keep,
+            ..options.clone()
+        };
+        assert!(matches!(
+            link_binary(&outside, &link, &keep),
+            Err(SymlinkError::SourceOutsideTrustedRoots(_))
+        ));
+    }
+
+    #[test]
+    fn verify_and_link_only_links_binaries_that_verify() {
+        let dir = TempDir::new();
+        let good = write_script(&dir.join("good"), "echo 'good 1.0.0'");
+        let link = dir.join("tool");
+        let (bin, outcome) = verify_and_link(&good, &link, ReplacePolicy::Replace).unwrap();
+        assert_eq!(bin.version_report(), "good 1.0.0");
+        assert_eq!(outcome, SymlinkOutcome::Created);
+        assert_eq!(fs::read_link(&link).unwrap(), good);
+
+        let broken = write_script(&dir.join("broken"), "exit 1");
+        let error = verify_and_link(&broken, &link, ReplacePolicy::Replace).unwrap_err();
+        assert!(matches!(
+            error,
+            LinkError::Verification(ExecutableVerificationError::VersionCallFail(_))
+        ));
+        assert_eq!(fs::read_link(&link).unwrap(), good);
+        let fresh = dir.join("fresh");
+        assert!(verify_and_link(&broken, &fresh, ReplacePolicy::Replace).is_err());
+        assert!(fs::symlink_metadata(&fresh).is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn created_parents_get_the_requested_umask() {
+        use std::os::unix::fs::PermissionsExt;
+
+        fn current_umask() -> libc::mode_t {
+            // Reading the umask means briefly setting it, so hold off other guards
+            let _lock = UMASK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            // SAFETY: umask only swaps the process's file mode creation mask
+            unsafe {
+                let mask = libc::umask(0o022);
+                libc::umask(mask);
+                mask
+            }
+        }
+
+        let dir = TempDir::new();
+        let source = tool(&dir.join("tool-1"), "tool 1.0.0");
+        let link = dir.join("a/b/tool");
+        let options = LinkOptions {
+            create_parents: true,
+            umask: Some(0o027),
+            ..LinkOptions::default()
+        };
+        let before = current_umask();
+
+        assert_eq!(
+            link_binary(&source, &link, &options).unwrap(),
+            SymlinkOutcome::Created
+        );
+        for parent in [dir.join("a"), dir.join("a/b")] {
+            let mode = fs::metadata(&parent).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o750, "{}", parent.display());
+        }
+        assert_eq!(current_umask(), before);
+    }
+
+    #[test]
+    fn pin_checksum_catches_a_source_swapped_mid_link() {
+        let dir = TempDir::new();
+        let source = tool(&dir.join("tool"), "tool 1.0.0");
+        let link = dir.join("link");
+        fs::write(&link, "in the way").unwrap();
+        let options = LinkOptions {
+            pin_checksum: true,
+            ..LinkOptions::default()
+        };
+
+        let swapped = source.path().to_path_buf();
+        AFTER_PIN.set(Some(Box::new(move || {
+            // Same length, so only the checksum can tell
+            write_script(&swapped, "echo 'evil 1.0.0'");
+        })));
+        assert!(matches!(
+            link_binary(&source, &link, &options),
+            Err(SymlinkError::SourceChangedDuringOperation(path)) if path == source.path()
+        ));
+        assert_eq!(fs::read_to_string(&link).unwrap(), "in the way");
+
+        // A change before linking starts is caught by the verification stamp
+        write_script(source.path(), "echo 'tool 1.0.0 (rebuilt)'");
+        assert!(matches!(
+            link_binary(&source, &link, &options),
+            Err(SymlinkError::SourceChangedDuringOperation(_))
+        ));
+        assert_eq!(fs::read_to_string(&link).unwrap(), "in the way");
+    }
+
+    #[test]
+    fn link_dir_into_refuses_to_exceed_max_links() {
+        let dir = TempDir::new();
+        let (sources, target) = (dir.join("src"), dir.join("bin"));
+        fs::create_dir(&sources).unwrap();
+        fs::create_dir(&target).unwrap();
+        for name in ["a", "b", "c"] {
+            tool(&sources.join(name), &format!("{name} 1.0.0"));
+        }
+        unix_fs::symlink(sources.join("a"), target.join("a")).unwrap();
+        unix_fs::symlink("../src/b", target.join("b")).unwrap();
+        // Links into other directories aren't managed, so they don't count
+        unix_fs::symlink("/bin/true", target.join("other")).unwrap();
+        unix_fs::symlink("/bin/true", target.join("c")).unwrap();
+        let options = LinkOptions {
+            max_links: Some(2),
+            ..LinkOptions::default()
+        };
+
+        assert!(matches!(
+            link_dir_into(&sources, &target, &options),
+            Err(SymlinkError::LinkLimitExceeded {
+                limit: 2,
+                would_be: 3
+            })
+        ));
+        let mut names = fs::read_dir(&target)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a", "b", "c", "other"]);
+        assert_eq!(
+            fs::read_link(target.join("c")).unwrap(),
+            Path::new("/bin/true")
+        );
+
+        let options = LinkOptions {
+            max_links: Some(3),
+            ..options
+        };
+        assert_eq!(link_dir_into(&sources, &target, &options).unwrap().len(), 3);
+        assert_eq!(fs::read_link(target.join("c")).unwrap(), sources.join("c"));
+        assert!(matches!(
+            link_dir_into(&dir.join("missing"), &target, &options),
+            Err(SymlinkError::ReadDirFailed(path, _)) if path == dir.join("missing")
+        ));
+
+        // A target that can't be listed is an error, one that doesn't exist yet is empty
+        let file = dir.join("file");
+        fs::write(&file, "").unwrap();
+        assert!(matches!(
+            link_dir_into(&sources, &file, &options),
+            Err(SymlinkError::ReadDirFailed(path, _)) if path == file
+        ));
+        let outcomes = link_dir_into(&sources, &dir.join("nowhere"), &options).unwrap();
+        assert_eq!(outcomes.len(), 3);
+    }
+}
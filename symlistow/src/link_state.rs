@@ -0,0 +1,569 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CString, OsString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{ExecutableBin, ExecutableVerificationError, SymlinkError};
+
+/// Suffix counter keeping the temporary links of concurrent swaps apart.
+static NEXT_TEMP_LINK: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether `link_path` resolves to a regular file the current user may execute.
+///
+/// The check uses the effective user and group, as `exec` does. A link that can't
+/// be resolved is an error.
+pub fn link_is_runnable(link_path: &Path) -> io::Result<bool> {
+    let target = fs::canonicalize(link_path)?;
+    if !fs::metadata(&target)?.is_file() {
+        return Ok(false);
+    }
+    let target = CString::new(target.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `target` is a valid NUL-terminated string that outlives the call
+    let allowed = unsafe {
+        libc::faccessat(
+            libc::AT_FDCWD,
+            target.as_ptr(),
+            libc::X_OK,
+            libc::AT_EACCESS,
+        )
+    };
+    Ok(allowed == 0)
+}
+
+/// Describes the symlink at `link_path` the way `ls -l` shows it, e.g.
+/// `git -> /opt/git/2.43/bin/git`.
+///
+/// A link whose target doesn't resolve gets a ` (broken)` suffix. Anything that
+/// isn't a symlink is an error, as from [`fs::read_link`].
+pub fn describe_link(link_path: &Path) -> io::Result<String> {
+    let target = fs::read_link(link_path)?;
+    let name = link_path.file_name().unwrap_or(link_path.as_os_str());
+    let mut description = format!("{} -> {}", name.to_string_lossy(), target.display());
+    if fs::metadata(link_path).is_err() {
+        description.push_str(" (broken)");
+    }
+    Ok(description)
+}
+
+/// Follows the symlink chain starting at `link_path` and returns the links that
+/// form a cycle, if it runs into one, e.g. `[a, b]` for `a -> b -> a`.
+///
+/// Where [`fs::canonicalize`] only fails with `ELOOP`, this names the links
+/// involved. A chain leading into a cycle from outside returns just the cycle.
+/// Each link's directory is resolved, so the links are absolute paths.
+///
+/// # Returns
+/// * `None` if the chain ends, at a file or dangling, without repeating itself
+pub fn detect_symlink_cycle(link_path: &Path) -> io::Result<Option<Vec<PathBuf>>> {
+    let Some(mut current) = resolve_parent(link_path)? else {
+        return Ok(None);
+    };
+    let mut chain: Vec<PathBuf> = Vec::new();
+    loop {
+        if let Some(start) = chain.iter().position(|seen| *seen == current) {
+            return Ok(Some(chain.split_off(start)));
+        }
+        let is_link = match fs::symlink_metadata(&current) {
+            Ok(metadata) => metadata.file_type().is_symlink(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+        if !is_link {
+            return Ok(None);
+        }
+        let target = fs::read_link(&current)?;
+        let next = match current.parent() {
+            Some(dir) => dir.join(target),
+            None => target,
+        };
+        chain.push(current);
+        match resolve_parent(&next)? {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// `path` with its directory fully resolved but its last component kept, or
+/// `None` if the directory doesn't exist.
+fn resolve_parent(path: &Path) -> io::Result<Option<PathBuf>> {
+    let (dir, name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => (dir, name),
+        // A path ending in `..` or a root is a directory, never a link
+        _ => return fs::canonicalize(path).map(Some),
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    match fs::canonicalize(dir) {
+        Ok(dir) => Ok(Some(dir.join(name))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Atomically repoints the symlink `current_link` at `version_dir`.
+///
+/// The new link is created beside `current_link` and renamed over it, so at every
+/// moment `current_link` resolves to either the old or the new directory. A
+/// relative `version_dir` is stored as given and so is resolved from the link's
+/// directory, as usual for symlinks.
+///
+/// # Errors
+/// * [`SymlinkError::ResolveFailed`] if `version_dir` isn't an existing directory
+/// * [`SymlinkError::CreateFailed`] if the link can't be created or renamed into
+///   place, e.g. because `current_link` is a real directory
+pub fn set_current(current_link: &Path, version_dir: &Path) -> Result<(), SymlinkError> {
+    let resolved = match current_link.parent() {
+        Some(dir) => dir.join(version_dir),
+        None => version_dir.to_path_buf(),
+    };
+    let metadata =
+        fs::metadata(&resolved).map_err(|e| SymlinkError::ResolveFailed(resolved.clone(), e))?;
+    if !metadata.is_dir() {
+        return Err(SymlinkError::ResolveFailed(
+            resolved,
+            io::Error::new(io::ErrorKind::NotADirectory, "not a directory"),
+        ));
+    }
+
+    swap_symlink(current_link, version_dir)
+        .map_err(|e| SymlinkError::CreateFailed(current_link.into(), e))
+}
+
+/// The symlinks directly inside a directory, by name, with their raw targets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkSnapshot {
+    /// Raw link targets keyed by file name.
+    pub links: BTreeMap<OsString, PathBuf>,
+}
+
+/// Records every symlink directly inside `dir` and its raw target.
+///
+/// Other entries are ignored, and links are not followed.
+pub fn snapshot_links(dir: &Path) -> io::Result<LinkSnapshot> {
+    let mut links = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_symlink() {
+            links.insert(entry.file_name(), fs::read_link(entry.path())?);
+        }
+    }
+    Ok(LinkSnapshot { links })
+}
+
+/// What [`audit_links`] found for one symlink.
+#[derive(Debug)]
+pub enum LinkAuditStatus {
+    /// The link and its source report the same version.
+    Agree { version_report: String },
+    /// Running the binary through the link reports something else than running
+    /// its source, e.g. a multi-call binary keyed on a stale link name.
+    Drifted {
+        link_report: String,
+        source_report: String,
+    },
+    /// The link dangles: nothing exists at its source.
+    SourceMissing,
+    /// The source exists, but it or the link fails verification.
+    Broken(ExecutableVerificationError),
+}
+
+/// The audit of one symlink in a link directory.
+#[derive(Debug)]
+pub struct LinkAudit {
+    pub link: PathBuf,
+    /// The fully resolved source, or the raw target (relative targets joined to
+    /// the link's directory) when the link dangles.
+    pub source: PathBuf,
+    pub status: LinkAuditStatus,
+}
+
+/// Verifies every symlink directly inside `link_dir` and its source, and
+/// reports whether their versions agree.
+///
+/// Links come in file name order. Other entries are ignored, and a directory
+/// that can't be read has nothing to audit.
+pub fn audit_links(link_dir: &Path) -> Vec<LinkAudit> {
+    let snapshot = snapshot_links(link_dir).unwrap_or_default();
+    snapshot
+        .links
+        .into_iter()
+        .map(|(name, target)| {
+            let link = link_dir.join(name);
+            let Ok(source) = fs::canonicalize(&link) else {
+                let source = link_dir.join(target);
+                let status = LinkAuditStatus::SourceMissing;
+                return LinkAudit {
+                    link,
+                    source,
+                    status,
+                };
+            };
+            let status = match (ExecutableBin::new(&link), ExecutableBin::new(&source)) {
+                (Ok(via_link), Ok(direct))
+                    if via_link.version_report() == direct.version_report() =>
+                {
+                    LinkAuditStatus::Agree {
+                        version_report: direct.version_report().into(),
+                    }
+                }
+                (Ok(via_link), Ok(direct)) => LinkAuditStatus::Drifted {
+                    link_report: via_link.version_report().into(),
+                    source_report: direct.version_report().into(),
+                },
+                (Err(e), _) | (_, Err(e)) => LinkAuditStatus::Broken(e),
+            };
+            LinkAudit {
+                link,
+                source,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// Groups the symlinks directly inside `link_dir` that resolve to the same file.
+///
+/// Only groups of two or more are returned, e.g. `vi` and `vim` both leading to
+/// one binary. Links are in file name order within a group, and groups are
+/// ordered by their first link. Dangling links and other entries are ignored,
+/// and a directory that can't be read has no redundant links.
+pub fn find_redundant_links(link_dir: &Path) -> Vec<Vec<PathBuf>> {
+    let snapshot = snapshot_links(link_dir).unwrap_or_default();
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+    let mut index_of = HashMap::new();
+    for name in snapshot.links.into_keys() {
+        let link = link_dir.join(name);
+        let Ok(resolved) = fs::canonicalize(&link) else {
+            continue;
+        };
+        let index = *index_of.entry(resolved).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(link);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Returns the symlinks in `dir` to the state recorded in `snapshot`.
+///
+/// Symlinks missing from the snapshot are removed, and every recorded link is
+/// recreated or repointed at its recorded target. Entries that aren't symlinks
+/// are left alone; one sitting where a recorded link belongs is an
+/// [`io::ErrorKind::AlreadyExists`] error.
+pub fn restore_links(dir: &Path, snapshot: &LinkSnapshot) -> io::Result<()> {
+    let current = snapshot_links(dir)?;
+    for name in current.links.keys() {
+        if !snapshot.links.contains_key(name) {
+            fs::remove_file(dir.join(name))?;
+        }
+    }
+    for (name, target) in &snapshot.links {
+        if current.links.get(name) == Some(target) {
+            continue;
+        }
+        let link = dir.join(name);
+        if !current.links.contains_key(name) && fs::symlink_metadata(&link).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is no longer a symlink", link.display()),
+            ));
+        }
+        swap_symlink(&link, target)?;
+    }
+    Ok(())
+}
+
+/// Points the symlink `link` at `target` by renaming a new link over it, so the
+/// path is never missing.
+fn swap_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    let name = link.file_name().map(OsString::from).unwrap_or_default();
+    let temp_link = loop {
+        let mut candidate = name.clone();
+        candidate.push(format!(
+            ".symlistow-tmp-{}-{}",
+            std::process::id(),
+            NEXT_TEMP_LINK.fetch_add(1, Ordering::Relaxed)
+        ));
+        let candidate = link.with_file_name(candidate);
+        match unix_fs::symlink(target, &candidate) {
+            Ok(()) => break candidate,
+            // Someone else's temp link or a leftover, neither is ours to remove
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    };
+    fs::rename(&temp_link, link).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_link);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn link_is_runnable_requires_an_executable_file() {
+        let dir = TempDir::new();
+        let script = write_script(&dir.join("tool"), "echo tool 1.0.0");
+        fs::write(dir.join("data"), "not a program").unwrap();
+        for (link, target) in [
+            ("runnable", script.clone()),
+            ("to-data", dir.join("data")),
+            ("to-dir", dir.path().into()),
+            ("broken", dir.join("missing")),
+        ] {
+            unix_fs::symlink(target, dir.join(link)).unwrap();
+        }
+
+        assert!(link_is_runnable(&dir.join("runnable")).unwrap());
+        assert!(!link_is_runnable(&dir.join("to-data")).unwrap());
+        assert!(!link_is_runnable(&dir.join("to-dir")).unwrap());
+        assert!(link_is_runnable(&dir.join("broken")).is_err());
+    }
+
+    #[test]
+    fn set_current_switches_without_the_link_ever_missing() {
+        let dir = TempDir::new();
+        let (v1, v2) = (dir.join("v1"), dir.join("v2"));
+        fs::create_dir(&v1).unwrap();
+        fs::create_dir(&v2).unwrap();
+        let current = dir.join("current");
+        set_current(&current, &v1).unwrap();
+        assert_eq!(fs::read_link(&current).unwrap(), v1);
+
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for n in 0..200 {
+                    set_current(&current, if n % 2 == 0 { &v2 } else { &v1 }).unwrap();
+                }
+                done.store(true, Ordering::Release);
+            });
+            while !done.load(Ordering::Acquire) {
+                let target = fs::read_link(&current).expect("current went missing");
+                assert!(target == v1 || target == v2, "{}", target.display());
+            }
+        });
+        assert_eq!(fs::read_link(&current).unwrap(), v1);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        fs::write(dir.join("file"), "").unwrap();
+        for bad in [dir.join("missing"), dir.join("file")] {
+            assert!(matches!(
+                set_current(&current, &bad),
+                Err(SymlinkError::ResolveFailed(path, _)) if path == bad
+            ));
+        }
+        assert_eq!(fs::read_link(&current).unwrap(), v1);
+    }
+
+    #[test]
+    fn set_current_leaves_other_temp_links_alone() {
+        let dir = TempDir::new();
+        let (v1, v2) = (dir.join("v1"), dir.join("v2"));
+        fs::create_dir(&v1).unwrap();
+        fs::create_dir(&v2).unwrap();
+        let current = dir.join("current");
+        // Squat the next few temp names, as a concurrent call or crashed run would
+        let next = NEXT_TEMP_LINK.load(Ordering::Relaxed);
+        let squatted = (next..next + 3)
+            .map(|n| dir.join(format!("current.symlistow-tmp-{}-{n}", std::process::id())))
+            .collect::<Vec<_>>();
+        for path in &squatted {
+            fs::write(path, "in use").unwrap();
+        }
+
+        set_current(&current, &v1).unwrap();
+        assert_eq!(fs::read_link(&current).unwrap(), v1);
+        for path in &squatted {
+            assert_eq!(fs::read_to_string(path).unwrap(), "in use");
+        }
+
+        // Callers on several threads never trip over each other's temp links
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for n in 0..50 {
+                        set_current(&current, if n % 2 == 0 { &v2 } else { &v1 }).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(fs::read_link(&current).unwrap(), v1);
+        assert_eq!(
+            fs::read_dir(dir.path()).unwrap().count(),
+            3 + squatted.len()
+        );
+    }
+
+    #[test]
+    fn restore_links_returns_the_directory_to_its_snapshot() {
+        let dir = TempDir::new();
+        for (link, target) in [("a", "target-a"), ("b", "../elsewhere/b"), ("c", "/abs/c")] {
+            unix_fs::symlink(target, dir.join(link)).unwrap();
+        }
+        fs::write(dir.join("file"), "not a link").unwrap();
+        let snapshot = snapshot_links(dir.path()).unwrap();
+        assert_eq!(snapshot.links.len(), 3);
+
+        fs::remove_file(dir.join("a")).unwrap();
+        fs::remove_file(dir.join("b")).unwrap();
+        unix_fs::symlink("repointed", dir.join("b")).unwrap();
+        unix_fs::symlink("added", dir.join("d")).unwrap();
+        assert_ne!(snapshot_links(dir.path()).unwrap(), snapshot);
+
+        restore_links(dir.path(), &snapshot).unwrap();
+        assert_eq!(snapshot_links(dir.path()).unwrap(), snapshot);
+        assert_eq!(
+            fs::read_link(dir.join("b")).unwrap(),
+            Path::new("../elsewhere/b")
+        );
+        assert_eq!(fs::read_to_string(dir.join("file")).unwrap(), "not a link");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 4);
+
+        // A non-link where a recorded link belongs is left alone
+        fs::remove_file(dir.join("a")).unwrap();
+        fs::write(dir.join("a"), "real file").unwrap();
+        let error = restore_links(dir.path(), &snapshot).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "real file");
+    }
+
+    #[test]
+    fn describe_link_shows_the_target_and_whether_it_dangles() {
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("git-2.43"), "echo 'git version 2.43.0'");
+        unix_fs::symlink(&tool, dir.join("git")).unwrap();
+        unix_fs::symlink("../missing/git", dir.join("stale")).unwrap();
+
+        assert_eq!(
+            describe_link(&dir.join("git")).unwrap(),
+            format!("git -> {}", tool.display())
+        );
+        assert_eq!(
+            describe_link(&dir.join("stale")).unwrap(),
+            "stale -> ../missing/git (broken)"
+        );
+        assert!(describe_link(&tool).is_err());
+    }
+
+    #[test]
+    fn audit_links_compares_each_link_with_its_source() {
+        let dir = TempDir::new();
+        let (bin, links) = (dir.join("bin"), dir.join("links"));
+        fs::create_dir(&bin).unwrap();
+        fs::create_dir(&links).unwrap();
+        let tool = write_script(&bin.join("tool"), "echo 'tool 1.0.0'");
+        let multicall = write_script(&bin.join("multicall"), r#"echo "$(basename "$0") 1.36.1""#);
+        let failing = write_script(&bin.join("failing"), "exit 1");
+        unix_fs::symlink(&tool, links.join("tool")).unwrap();
+        unix_fs::symlink(&multicall, links.join("applet")).unwrap();
+        unix_fs::symlink(&failing, links.join("broken")).unwrap();
+        unix_fs::symlink("gone", links.join("dangling")).unwrap();
+        fs::write(links.join("notes"), "not a link").unwrap();
+
+        let audits = audit_links(&links);
+        let names = audits
+            .iter()
+            .map(|audit| audit.link.strip_prefix(&links).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["applet", "broken", "dangling", "tool"].map(PathBuf::from)
+        );
+
+        assert_eq!(audits[0].source, multicall);
+        assert!(matches!(
+            &audits[0].status,
+            LinkAuditStatus::Drifted { link_report, source_report }
+                if link_report == "applet 1.36.1" && source_report == "multicall 1.36.1"
+        ));
+        assert!(matches!(
+            &audits[1].status,
+            LinkAuditStatus::Broken(ExecutableVerificationError::VersionCallFail(_))
+        ));
+        assert_eq!(audits[2].source, links.join("gone"));
+        assert!(matches!(audits[2].status, LinkAuditStatus::SourceMissing));
+        assert_eq!(audits[3].source, tool);
+        assert!(matches!(
+            &audits[3].status,
+            LinkAuditStatus::Agree { version_report } if version_report == "tool 1.0.0"
+        ));
+    }
+
+    #[test]
+    fn find_redundant_links_groups_links_to_one_source() {
+        let dir = TempDir::new();
+        let (bin, links) = (dir.join("bin"), dir.join("links"));
+        fs::create_dir(&bin).unwrap();
+        fs::create_dir(&links).unwrap();
+        let vim = write_script(&bin.join("vim"), "echo 'VIM 9.1'");
+        let rg = write_script(&bin.join("rg"), "echo 'ripgrep 14.1.0'");
+        unix_fs::symlink(&vim, links.join("vim")).unwrap();
+        unix_fs::symlink(&vim, links.join("vi")).unwrap();
+        // A chain still resolves to the same file
+        unix_fs::symlink("vi", links.join("view")).unwrap();
+        unix_fs::symlink(&rg, links.join("rg")).unwrap();
+        unix_fs::symlink("gone", links.join("dangling")).unwrap();
+        unix_fs::symlink("gone", links.join("dangling-too")).unwrap();
+
+        assert_eq!(
+            find_redundant_links(&links),
+            [vec![
+                links.join("vi"),
+                links.join("view"),
+                links.join("vim")
+            ]]
+        );
+        assert!(find_redundant_links(&dir.join("missing")).is_empty());
+    }
+
+    #[test]
+    fn detect_symlink_cycle_names_the_links_in_the_loop() {
+        let dir = TempDir::new();
+        unix_fs::symlink("b", dir.join("a")).unwrap();
+        unix_fs::symlink(dir.join("a"), dir.join("b")).unwrap();
+        // A chain leading into the cycle reports only the cycle
+        unix_fs::symlink("a", dir.join("entry")).unwrap();
+        let tool = write_script(&dir.join("tool"), "echo tool 1.0.0");
+        unix_fs::symlink(&tool, dir.join("fine")).unwrap();
+        unix_fs::symlink("gone", dir.join("dangling")).unwrap();
+
+        let cycle = [dir.join("a"), dir.join("b")];
+        assert_eq!(
+            detect_symlink_cycle(&dir.join("a")).unwrap().unwrap(),
+            cycle
+        );
+        assert_eq!(
+            detect_symlink_cycle(&dir.join("entry")).unwrap().unwrap(),
+            cycle
+        );
+        assert_eq!(
+            detect_symlink_cycle(&dir.join("b")).unwrap().unwrap(),
+            [dir.join("b"), dir.join("a")]
+        );
+        unix_fs::symlink("self", dir.join("self")).unwrap();
+        assert_eq!(
+            detect_symlink_cycle(&dir.join("self")).unwrap().unwrap(),
+            [dir.join("self")]
+        );
+        for path in [dir.join("fine"), dir.join("dangling"), tool] {
+            assert_eq!(detect_symlink_cycle(&path).unwrap(), None, "{path:?}");
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs as unix_fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::SymlinkError;
+use crate::inspect::sha256_file;
+
+/// A way of making a path provide a source binary.
+///
+/// The link flow moves anything already at the target aside before calling
+/// [`Linker::link`], so implementations only ever create into an empty path.
+pub trait Linker {
+    /// Makes `target` provide `source`.
+    fn link(&self, source: &Path, target: &Path) -> Result<(), SymlinkError>;
+
+    /// The file `link` currently provides, or `None` if there is nothing this
+    /// backend recognizes there.
+    fn current_target(&self, link: &Path) -> io::Result<Option<PathBuf>>;
+
+    /// Whether the link records the source's path, so that a relative source path
+    /// is resolved from the link's directory. Only such backends honor
+    /// [`crate::LinkOptions::relative`].
+    fn stores_target_path(&self) -> bool {
+        false
+    }
+
+    /// Whether `link` already provides `source`, so linking can be skipped.
+    ///
+    /// By default this compares the resolved [`Linker::current_target`] with the
+    /// resolved source.
+    fn is_current(&self, source: &Path, link: &Path) -> io::Result<bool> {
+        let Some(target) = self.current_target(link)? else {
+            return Ok(false);
+        };
+        // Relative targets are relative to the link's directory
+        let target = match link.parent() {
+            Some(dir) => dir.join(target),
+            None => target,
+        };
+        Ok(fs::canonicalize(target).ok() == Some(fs::canonicalize(source)?))
+    }
+}
+
+/// Links with a symbolic link to the source.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SymlinkLinker;
+
+impl Linker for SymlinkLinker {
+    fn link(&self, source: &Path, target: &Path) -> Result<(), SymlinkError> {
+        unix_fs::symlink(source, target).map_err(|e| SymlinkError::CreateFailed(target.into(), e))
+    }
+
+    fn stores_target_path(&self) -> bool {
+        true
+    }
+
+    /// The raw target of the symlink at `link`; anything that isn't a symlink is `None`.
+    fn current_target(&self, link: &Path) -> io::Result<Option<PathBuf>> {
+        match fs::symlink_metadata(link) {
+            Ok(metadata) if metadata.file_type().is_symlink() => fs::read_link(link).map(Some),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Links with a hard link, so the target is the source file itself.
+///
+/// Hard links can't cross filesystems.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HardlinkLinker;
+
+impl Linker for HardlinkLinker {
+    fn link(&self, source: &Path, target: &Path) -> Result<(), SymlinkError> {
+        fs::hard_link(source, target).map_err(|e| SymlinkError::CreateFailed(target.into(), e))
+    }
+
+    /// `link` itself when it is a regular file.
+    fn current_target(&self, link: &Path) -> io::Result<Option<PathBuf>> {
+        existing_file(link)
+    }
+
+    /// Whether `link` and `source` are the same inode on the same device.
+    fn is_current(&self, source: &Path, link: &Path) -> io::Result<bool> {
+        let Some(link) = self.current_target(link)? else {
+            return Ok(false);
+        };
+        let (link, source) = (fs::symlink_metadata(link)?, fs::metadata(source)?);
+        Ok(link.dev() == source.dev() && link.ino() == source.ino())
+    }
+}
+
+/// Installs an independent copy of the source.
+///
+/// The copy keeps the source's permission bits, so it stays executable, and is
+/// useful where the source may disappear (e.g. an ephemeral container layer).
+///
+/// Before copying, the free space of the target's filesystem is checked so a
+/// full disk can't leave a truncated copy behind: the copy is refused with
+/// [`SymlinkError::InsufficientSpace`] unless the source fits while leaving
+/// [`CopyLinker::DEFAULT_MARGIN`] bytes free, or the margin given to
+/// [`CopyLinker::with_margin`].
+#[derive(Debug, Clone, Copy)]
+pub struct CopyLinker {
+    margin: u64,
+    available_space: fn(&Path) -> io::Result<u64>,
+}
+
+impl CopyLinker {
+    /// Bytes left free on the target filesystem by default, 1 MiB.
+    pub const DEFAULT_MARGIN: u64 = 1024 * 1024;
+
+    pub fn new() -> Self {
+        Self {
+            margin: Self::DEFAULT_MARGIN,
+            available_space,
+        }
+    }
+
+    /// A copy linker leaving `margin` bytes free on the target filesystem.
+    pub fn with_margin(self, margin: u64) -> Self {
+        Self { margin, ..self }
+    }
+
+    /// A copy linker measuring free space with `available_space`, which is given
+    /// the directory the copy is made in.
+    pub fn with_available_space(self, available_space: fn(&Path) -> io::Result<u64>) -> Self {
+        Self {
+            available_space,
+            ..self
+        }
+    }
+}
+
+impl Default for CopyLinker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linker for CopyLinker {
+    fn link(&self, source: &Path, target: &Path) -> Result<(), SymlinkError> {
+        let create_failed = |e| SymlinkError::CreateFailed(target.into(), e);
+        let needed = fs::metadata(source).map_err(create_failed)?.len();
+        let dir = match target.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let available = (self.available_space)(dir).map_err(create_failed)?;
+        if needed > available.saturating_sub(self.margin) {
+            return Err(SymlinkError::InsufficientSpace { needed, available });
+        }
+        // fs::copy carries the permission bits over on Unix
+        fs::copy(source, target).map(drop).map_err(create_failed)
+    }
+
+    /// `link` itself when it is a regular file.
+    fn current_target(&self, link: &Path) -> io::Result<Option<PathBuf>> {
+        existing_file(link)
+    }
+
+    /// Whether the copy at `link` has the same size and sha256 as `source`.
+    fn is_current(&self, source: &Path, link: &Path) -> io::Result<bool> {
+        let Some(link) = self.current_target(link)? else {
+            return Ok(false);
+        };
+        if fs::metadata(&link)?.len() != fs::metadata(source)?.len() {
+            return Ok(false);
+        }
+        Ok(sha256_file(&link)? == sha256_file(source)?)
+    }
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`.
+fn available_space(path: &Path) -> io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stats` is valid for writes
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: statvfs succeeded, so it initialized `stats`
+    let stats = unsafe { stats.assume_init() };
+    // The field types are narrower than u64 on some platforms
+    #[allow(clippy::useless_conversion)]
+    Ok(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)))
+}
+
+/// `path` if it is a regular file, without following links.
+fn existing_file(path: &Path) -> io::Result<Option<PathBuf>> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_file() => Ok(Some(path.into())),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+    use crate::{ExecutableBin, LinkOptions, SymlinkOutcome, link_binary_with};
+
+    #[test]
+    fn each_backend_links_and_reports_its_current_target() {
+        let dir = TempDir::new();
+        let source = write_script(&dir.join("tool"), "echo tool 1.0.0");
+        let other = write_script(&dir.join("other"), "echo other 1.0.0");
+        let backends: [(&str, &dyn Linker); 3] = [
+            ("symlink", &SymlinkLinker),
+            ("hardlink", &HardlinkLinker),
+            ("copy", &CopyLinker::new()),
+        ];
+        for (name, linker) in backends {
+            let target = dir.join(name);
+            assert_eq!(linker.current_target(&target).unwrap(), None, "{name}");
+            linker.link(&source, &target).unwrap();
+
+            let expected = if name == "symlink" { &source } else { &target };
+            assert_eq!(
+                linker.current_target(&target).unwrap().as_ref(),
+                Some(expected),
+                "{name}"
+            );
+            assert!(linker.is_current(&source, &target).unwrap(), "{name}");
+            assert!(!linker.is_current(&other, &target).unwrap(), "{name}");
+        }
+        // Each backend only recognizes its own kind of entry
+        assert_eq!(
+            SymlinkLinker.current_target(&dir.join("copy")).unwrap(),
+            None
+        );
+        assert_eq!(
+            HardlinkLinker.current_target(&dir.join("symlink")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn copy_linker_installs_an_executable_copy_once() {
+        let dir = TempDir::new();
+        let source =
+            ExecutableBin::new(write_script(&dir.join("tool"), "echo tool 1.0.0")).unwrap();
+        let target = dir.join("copy");
+        let options = LinkOptions::default();
+        let linker = CopyLinker::new();
+
+        let outcome = link_binary_with(&source, &target, &options, &linker).unwrap();
+        assert_eq!(outcome, SymlinkOutcome::Created);
+        let metadata = fs::symlink_metadata(&target).unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o755);
+        assert_eq!(
+            ExecutableBin::new(&target).unwrap().version_report(),
+            "tool 1.0.0"
+        );
+
+        let again = link_binary_with(&source, &target, &options, &linker).unwrap();
+        assert_eq!(again, SymlinkOutcome::Unchanged);
+    }
+
+    #[test]
+    fn copy_linker_refuses_a_copy_that_would_fill_the_disk() {
+        let dir = TempDir::new();
+        let source = write_script(&dir.join("tool"), "echo tool 1.0.0");
+        let needed = fs::metadata(&source).unwrap().len();
+        let target = dir.join("copy");
+
+        let full = CopyLinker::new().with_available_space(|_| Ok(CopyLinker::DEFAULT_MARGIN + 1));
+        match full.link(&source, &target) {
+            Err(SymlinkError::InsufficientSpace {
+                needed: reported,
+                available,
+            }) => {
+                assert_eq!(reported, needed);
+                assert_eq!(available, CopyLinker::DEFAULT_MARGIN + 1);
+            }
+            other => panic!("expected InsufficientSpace, got {other:?}"),
+        }
+        assert!(fs::symlink_metadata(&target).is_err());
+
+        // The same space suffices once the margin is dropped
+        let roomy = full.with_margin(0);
+        roomy.link(&source, &target).unwrap();
+        assert_eq!(fs::metadata(&target).unwrap().len(), needed);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::search::{is_executable, verify_named};
+use crate::version::parse_version_report;
+use crate::{ExecutableBin, ExecutableVerificationError};
+
+/// How an installed tool compares to the version it is expected at.
+#[derive(Debug)]
+pub enum VersionCheck {
+    /// The tool verified and reports the expected version.
+    Match(ExecutableBin),
+    /// The tool verified but reports a different version.
+    Drift {
+        bin: ExecutableBin,
+        expected: String,
+    },
+    /// The tool is absent or failed verification.
+    Missing(ExecutableVerificationError),
+}
+
+impl VersionCheck {
+    /// Verifies `path` and compares its version report against `expected`.
+    ///
+    /// When both sides parse as semver the versions are compared, otherwise
+    /// `expected` must appear as a word of the version report.
+    pub fn check(path: &Path, expected: &str) -> Self {
+        match ExecutableBin::new(path) {
+            Ok(bin) if reports_version(&bin, expected) => VersionCheck::Match(bin),
+            Ok(bin) => VersionCheck::Drift {
+                bin,
+                expected: expected.to_string(),
+            },
+            Err(e) => VersionCheck::Missing(e),
+        }
+    }
+}
+
+fn reports_version(bin: &ExecutableBin, expected: &str) -> bool {
+    match (parse_version_report(expected), bin.version()) {
+        (Ok(expected), Ok(found)) => expected == found,
+        _ => bin
+            .version_report()
+            .split_whitespace()
+            .any(|word| word.trim_start_matches('v') == expected.trim_start_matches('v')),
+    }
+}
+
+/// Parses an asdf-style `.tool-versions` file into a map of tool name to version.
+///
+/// Each line is `name version [fallback...]`; only the first version is kept.
+/// Blank lines and `#` comments are ignored.
+pub fn parse_tool_versions(path: &Path) -> io::Result<BTreeMap<String, String>> {
+    let contents = fs::read_to_string(path)?;
+    let mut versions = BTreeMap::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        if let (Some(name), Some(version)) = (words.next(), words.next()) {
+            versions.insert(name.to_string(), version.to_string());
+        }
+    }
+    Ok(versions)
+}
+
+/// Parses a tool list file with one tool name per line.
+///
+/// Anything after a `#` is a comment, and blank lines are ignored.
+pub fn parse_tool_list(path: &Path) -> io::Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Finds each tool of the list file at `path` on `$PATH` and verifies it.
+///
+/// # Returns
+/// * Each tool name with its verification, in the order of the file
+pub fn verify_tool_list(
+    path: &Path,
+) -> io::Result<Vec<(String, Result<ExecutableBin, ExecutableVerificationError>)>> {
+    Ok(parse_tool_list(path)?
+        .into_iter()
+        .map(|name| {
+            let bin = verify_named(&name);
+            (name, bin)
+        })
+        .collect())
+}
+
+/// Checks each tool in `dir_with_tool_versions/.tool-versions` against the binary
+/// of the same name in `search_dir`.
+///
+/// Tool names are used as binary names, so asdf plugins whose binary is named
+/// differently (e.g. `nodejs` providing `node`) will be reported missing.
+pub fn verify_tool_versions(
+    dir_with_tool_versions: &Path,
+    search_dir: &Path,
+) -> io::Result<BTreeMap<String, VersionCheck>> {
+    let expected = parse_tool_versions(&dir_with_tool_versions.join(".tool-versions"))?;
+    Ok(expected
+        .into_iter()
+        .map(|(name, version)| {
+            let check = VersionCheck::check(&search_dir.join(&name), &version);
+            (name, check)
+        })
+        .collect())
+}
+
+/// How the executables in a directory compare to the tools expected there.
+#[derive(Debug, Default)]
+pub struct Reconciliation {
+    /// Expected tools that are present and verify, by name.
+    pub present: Vec<ExecutableBin>,
+    /// Expected tools that are present but fail verification.
+    pub failed: BTreeMap<String, ExecutableVerificationError>,
+    /// Expected tools with no executable of that name.
+    pub missing: Vec<String>,
+    /// Executables that aren't expected, by name.
+    pub unexpected: Vec<String>,
+}
+
+/// Compares the executables directly inside `dir` with `expected_names`.
+///
+/// Only expected tools are verified; unexpected ones are just listed. Every list
+/// is sorted by name.
+pub fn reconcile(expected_names: &[&str], dir: &Path) -> Reconciliation {
+    let expected = expected_names.iter().copied().collect::<BTreeSet<_>>();
+    let mut reconciliation = Reconciliation::default();
+    for name in &expected {
+        let path = dir.join(name);
+        if !is_executable(&path) {
+            reconciliation.missing.push(name.to_string());
+            continue;
+        }
+        match ExecutableBin::new(path) {
+            Ok(bin) => reconciliation.present.push(bin),
+            Err(e) => {
+                reconciliation.failed.insert(name.to_string(), e);
+            }
+        }
+    }
+
+    let found = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !expected.contains(name.as_str()));
+    reconciliation.unexpected = found.collect();
+    reconciliation.unexpected.sort();
+    reconciliation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, with_path_prefix, write_script};
+
+    #[test]
+    fn parse_tool_versions_skips_comments_and_fallbacks() {
+        let dir = TempDir::new();
+        let path = dir.join(".tool-versions");
+        fs::write(
+            &path,
+            "# pinned for CI\nnodejs 20.11.0 system\n\npython 3.12.1 # newest\nruby\n",
+        )
+        .unwrap();
+        let versions = parse_tool_versions(&path).unwrap();
+        assert_eq!(
+            versions.into_iter().collect::<Vec<_>>(),
+            [
+                ("nodejs".to_string(), "20.11.0".to_string()),
+                ("python".to_string(), "3.12.1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reconcile_reports_missing_and_unexpected_tools() {
+        let dir = TempDir::new();
+        write_script(&dir.join("cc"), "echo 'cc 13.2.0'");
+        write_script(&dir.join("ld"), "exit 1");
+        write_script(&dir.join("stray"), "echo 'stray 0.1.0'");
+        fs::write(dir.join("README"), "not executable").unwrap();
+
+        let reconciliation = reconcile(&["cc", "ld", "ar"], dir.path());
+        let present = reconciliation
+            .present
+            .iter()
+            .map(|bin| (bin.path(), bin.version_report()))
+            .collect::<Vec<_>>();
+        assert_eq!(present, [(dir.join("cc").as_path(), "cc 13.2.0")]);
+        assert_eq!(reconciliation.failed.keys().collect::<Vec<_>>(), ["ld"]);
+        assert_eq!(reconciliation.missing, ["ar"]);
+        assert_eq!(reconciliation.unexpected, ["stray"]);
+    }
+
+    #[test]
+    fn verify_tool_list_resolves_each_listed_tool_on_path() {
+        let dir = TempDir::new();
+        let bin = dir.join("bin");
+        fs::create_dir(&bin).unwrap();
+        write_script(&bin.join("symlistow-fmt"), "echo 'fmt 1.2.0'");
+        write_script(&bin.join("symlistow-lint"), "echo 'lint 0.9.1'");
+        let list = dir.join("Toolfile");
+        fs::write(
+            &list,
+            "# formatting\nsymlistow-fmt\n\n  symlistow-lint   # pinned elsewhere\n#symlistow-old\nsymlistow-absent\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            parse_tool_list(&list).unwrap(),
+            ["symlistow-fmt", "symlistow-lint", "symlistow-absent"]
+        );
+        let results = with_path_prefix(&[&bin], || verify_tool_list(&list).unwrap());
+        let names = results
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["symlistow-fmt", "symlistow-lint", "symlistow-absent"]
+        );
+        assert_eq!(
+            results[0].1.as_ref().unwrap().path(),
+            bin.join("symlistow-fmt")
+        );
+        assert_eq!(results[0].1.as_ref().unwrap().version_report(), "fmt 1.2.0");
+        assert_eq!(
+            results[1].1.as_ref().unwrap().version_report(),
+            "lint 0.9.1"
+        );
+        assert!(matches!(
+            &results[2].1,
+            Err(ExecutableVerificationError::NotOnPath(name)) if name == "symlistow-absent"
+        ));
+        assert!(verify_tool_list(&dir.join("missing")).is_err());
+    }
+}
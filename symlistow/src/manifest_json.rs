@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{LinkError, ReplacePolicy, SymlinkOutcome, VersionCheck, verify_and_link};
+
+/// One entry of a JSON deploy manifest.
+#[derive(Debug, serde::Deserialize)]
+struct ManifestEntry {
+    name: String,
+    path: PathBuf,
+    expected_version: String,
+}
+
+/// The result of checking one manifest entry.
+#[derive(Debug)]
+pub struct ManifestCheck {
+    pub name: String,
+    /// The entry's path, joined onto the manifest root.
+    pub path: PathBuf,
+    pub check: VersionCheck,
+}
+
+/// Checks every binary declared in a JSON manifest.
+///
+/// The manifest is an array of `{"name": ..., "path": ..., "expected_version": ...}`
+/// objects; relative paths are resolved against `root`.
+pub fn verify_manifest(
+    manifest_json: &str,
+    root: &Path,
+) -> Result<Vec<ManifestCheck>, serde_json::Error> {
+    let entries: Vec<ManifestEntry> = serde_json::from_str(manifest_json)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let path = root.join(&entry.path);
+            let check = VersionCheck::check(&path, &entry.expected_version);
+            ManifestCheck {
+                name: entry.name,
+                path,
+                check,
+            }
+        })
+        .collect())
+}
+
+/// A declarative set of links, as read by [`apply_link_spec`].
+#[derive(Debug, serde::Deserialize)]
+struct LinkSpec {
+    link: Vec<LinkSpecEntry>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct LinkSpecEntry {
+    source: PathBuf,
+    target: PathBuf,
+}
+
+/// Verifies and links every entry of a JSON link spec, in order.
+///
+/// The spec is an object `{"link": [{"source": ..., "target": ...}, ...]}`; relative
+/// paths are resolved against the spec's directory. Links that already point at
+/// their source are left alone, so re-applying an unchanged spec reports only
+/// [`SymlinkOutcome::Unchanged`]. The first failing entry stops the run, leaving
+/// the links before it in place.
+///
+/// # Arguments
+/// * `spec_path` - The spec file
+/// * `policy` - What to do where a target is taken by something else
+///
+/// # Returns
+/// * What linking did for each entry, in spec order
+pub fn apply_link_spec(
+    spec_path: &Path,
+    policy: ReplacePolicy,
+) -> Result<Vec<SymlinkOutcome>, LinkError> {
+    let invalid = |e| LinkError::InvalidSpec(spec_path.into(), e);
+    let spec: LinkSpec = serde_json::from_str(&fs::read_to_string(spec_path).map_err(invalid)?)
+        .map_err(|e| invalid(e.into()))?;
+    // Links store their source as given, so relative sources must not stay relative
+    let absolute = std::path::absolute(spec_path).map_err(invalid)?;
+    let root = absolute.parent().unwrap_or(Path::new("/"));
+    spec.link
+        .iter()
+        .map(|entry| {
+            let (source, target) = (root.join(&entry.source), root.join(&entry.target));
+            verify_and_link(&source, &target, policy).map(|(_, outcome)| outcome)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn verify_manifest_reports_match_drift_and_missing() {
+        let dir = TempDir::new();
+        fs::create_dir(dir.join("bin")).unwrap();
+        write_script(&dir.join("bin/jq"), "echo jq-1.7.1");
+        write_script(&dir.join("bin/rg"), "echo ripgrep 13.0.0");
+        let manifest = r#"[
+            {"name": "jq", "path": "bin/jq", "expected_version": "1.7.1"},
+            {"name": "rg", "path": "bin/rg", "expected_version": "14.1.0"},
+            {"name": "fd", "path": "bin/fd", "expected_version": "9.0.0"}
+        ]"#;
+        let checks = verify_manifest(manifest, dir.path()).unwrap();
+
+        let names = checks.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["jq", "rg", "fd"]);
+        assert_eq!(checks[0].path, dir.join("bin/jq"));
+        assert!(matches!(checks[0].check, VersionCheck::Match(_)));
+        assert!(
+            matches!(&checks[1].check, VersionCheck::Drift { bin, expected }
+                if bin.version_report() == "ripgrep 13.0.0" && expected == "14.1.0")
+        );
+        assert!(matches!(checks[2].check, VersionCheck::Missing(_)));
+        assert!(verify_manifest("{", dir.path()).is_err());
+    }
+
+    #[test]
+    fn apply_link_spec_is_idempotent() {
+        let dir = TempDir::new();
+        fs::create_dir_all(dir.join("opt/bin")).unwrap();
+        fs::create_dir(dir.join("links")).unwrap();
+        write_script(&dir.join("opt/bin/jq"), "echo jq-1.7.1");
+        write_script(&dir.join("opt/bin/rg"), "echo ripgrep 14.1.0");
+        fs::write(dir.join("links/rg"), "in the way").unwrap();
+        let spec = dir.join("links.json");
+        fs::write(
+            &spec,
+            r#"{"link": [
+                {"source": "opt/bin/jq", "target": "links/jq"},
+                {"source": "opt/bin/rg", "target": "links/rg"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let outcomes = apply_link_spec(&spec, ReplacePolicy::Replace).unwrap();
+        assert_eq!(
+            outcomes,
+            [SymlinkOutcome::Created, SymlinkOutcome::Replaced]
+        );
+        assert_eq!(
+            fs::read_link(dir.join("links/jq")).unwrap(),
+            dir.join("opt/bin/jq")
+        );
+        let again = apply_link_spec(&spec, ReplacePolicy::Fail).unwrap();
+        assert_eq!(again, [SymlinkOutcome::Unchanged; 2]);
+
+        fs::write(&spec, r#"{"links": []}"#).unwrap();
+        assert!(matches!(
+            apply_link_spec(&spec, ReplacePolicy::Replace),
+            Err(LinkError::InvalidSpec(path, _)) if path == spec
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ExecutableBin;
+
+/// Where a binary most likely came from, see [`ExecutableBin::provenance_guess`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Provenance {
+    /// The system package manager (apt, dnf, pacman, ...), under `/usr` or `/bin`.
+    System,
+    /// `cargo install`, under `$CARGO_HOME/bin` or `~/.cargo/bin`.
+    Cargo,
+    /// Homebrew, under `/opt/homebrew`, `/usr/local/Cellar` or Linuxbrew's prefix.
+    Homebrew,
+    /// Nix, under `/nix/store`.
+    Nix,
+    /// Snap, under `/snap`.
+    Snap,
+    /// A manual install under `/usr/local` or `/opt`.
+    Local,
+    /// A per-user install under `~/.local/bin`, e.g. by pipx.
+    User,
+    Unknown,
+}
+
+/// Prefixes that identify a source regardless of the user, most specific first.
+const SYSTEM_PREFIXES: &[(&str, Provenance)] = &[
+    ("/nix/store", Provenance::Nix),
+    ("/opt/homebrew", Provenance::Homebrew),
+    ("/usr/local/Cellar", Provenance::Homebrew),
+    ("/home/linuxbrew/.linuxbrew", Provenance::Homebrew),
+    ("/snap", Provenance::Snap),
+    ("/usr/local", Provenance::Local),
+    ("/opt", Provenance::Local),
+    ("/usr", Provenance::System),
+    ("/bin", Provenance::System),
+    ("/sbin", Provenance::System),
+];
+
+impl ExecutableBin {
+    /// Best-effort guess at how the binary was installed, from where it lives.
+    ///
+    /// The path is resolved first, so a `/usr/local/bin/git` link into
+    /// Homebrew's Cellar counts as [`Provenance::Homebrew`]. Nothing is run or
+    /// read beyond following links; a path no known prefix matches is
+    /// [`Provenance::Unknown`].
+    pub fn provenance_guess(&self) -> Provenance {
+        let path = fs::canonicalize(self.path()).unwrap_or_else(|_| self.path().into());
+        let home = env::var_os("HOME").map(PathBuf::from);
+        let cargo_home = env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".cargo")));
+        classify(&path, home.as_deref(), cargo_home.as_deref())
+    }
+}
+
+fn classify(path: &Path, home: Option<&Path>, cargo_home: Option<&Path>) -> Provenance {
+    if cargo_home.is_some_and(|cargo_home| path.starts_with(cargo_home.join("bin"))) {
+        return Provenance::Cargo;
+    }
+    if home.is_some_and(|home| path.starts_with(home.join(".local/bin"))) {
+        return Provenance::User;
+    }
+    SYSTEM_PREFIXES
+        .iter()
+        .find(|(prefix, _)| path.starts_with(prefix))
+        .map_or(Provenance::Unknown, |&(_, provenance)| provenance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn classify_prefers_the_most_specific_prefix() {
+        let home = Path::new("/home/me");
+        let cargo_home = Path::new("/usr/local/cargo");
+        let classify = |path: &str| classify(Path::new(path), Some(home), Some(cargo_home));
+        for (path, expected) in [
+            ("/usr/local/cargo/bin/rg", Provenance::Cargo),
+            ("/home/me/.local/bin/black", Provenance::User),
+            ("/usr/local/Cellar/git/2.43.0/bin/git", Provenance::Homebrew),
+            ("/opt/homebrew/bin/jq", Provenance::Homebrew),
+            ("/home/linuxbrew/.linuxbrew/bin/fd", Provenance::Homebrew),
+            ("/nix/store/abc-ripgrep-14.1.0/bin/rg", Provenance::Nix),
+            ("/snap/bin/lxc", Provenance::Snap),
+            ("/usr/local/bin/terraform", Provenance::Local),
+            ("/opt/tools/bin/tool", Provenance::Local),
+            ("/usr/bin/git", Provenance::System),
+            ("/bin/sh", Provenance::System),
+            ("/usr/local/cargo/registry/tool", Provenance::Local),
+            ("/home/me/bin/tool", Provenance::Unknown),
+            ("/usrlocal/bin/tool", Provenance::Unknown),
+        ] {
+            assert_eq!(classify(path), expected, "{path}");
+        }
+        assert_eq!(
+            super::classify(Path::new("/home/me/.cargo/bin/rg"), None, None),
+            Provenance::Unknown
+        );
+    }
+
+    #[test]
+    fn provenance_guess_follows_links() {
+        let dir = TempDir::new();
+        let link = dir.join("sh");
+        std::os::unix::fs::symlink("/bin/sh", &link).unwrap();
+        let bin = ExecutableBin::new_unverified(link, String::new());
+        assert_eq!(bin.provenance_guess(), Provenance::System);
+    }
+}
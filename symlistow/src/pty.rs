@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+use std::process::Stdio;
+use std::ptr;
+
+use crate::{ExecutableVerificationError, VerifyOptions, decode_report, version_command};
+
+/// Verifies a binary with its stdout attached to a pseudo-terminal.
+///
+/// Some tools only print their version, or print it differently, when stdout
+/// is a terminal. Stdin is closed and stderr discarded. Output read back from
+/// the terminal is decoded like any other report, so the `\r\n` line endings
+/// the terminal adds are normalized away.
+///
+/// # Arguments
+/// * `binary_path` - The binary to run
+/// * `version_arg` - The single argument that makes it print its version
+pub fn verify_binary_pty(
+    binary_path: &Path,
+    version_arg: &str,
+) -> Result<String, ExecutableVerificationError> {
+    let options = VerifyOptions {
+        version_args: vec![version_arg.into()],
+        ..VerifyOptions::default()
+    };
+    let mut command = version_command(binary_path, &options)?;
+    let (controller, terminal) = open_pty()?;
+    command
+        .stdin(Stdio::null())
+        .stdout(terminal)
+        .stderr(Stdio::null());
+    let mut child = command.spawn()?;
+    // The command holds our end of the terminal; drop it so reads end with the child
+    drop(command);
+
+    let output = read_until_hangup(File::from(controller))?;
+    let status = child.wait()?;
+    if status.success() {
+        Ok(decode_report(&output, &options))
+    } else {
+        Err(ExecutableVerificationError::VersionCallFail(status))
+    }
+}
+
+/// Opens a pseudo-terminal, returning its controlling side and its terminal.
+///
+/// Both descriptors are close-on-exec, so the child only gets the terminal
+/// through its stdio.
+fn open_pty() -> io::Result<(OwnedFd, OwnedFd)> {
+    let (mut controller, mut terminal) = (-1, -1);
+    // SAFETY: both out-pointers are valid, and the name, termios and winsize
+    // arguments may be null
+    let opened = unsafe {
+        libc::openpty(
+            &mut controller,
+            &mut terminal,
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null(),
+        )
+    };
+    if opened != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openpty succeeded, so both are open descriptors nothing else owns
+    let fds = unsafe {
+        (
+            OwnedFd::from_raw_fd(controller),
+            OwnedFd::from_raw_fd(terminal),
+        )
+    };
+    for fd in [&fds.0, &fds.1] {
+        // SAFETY: `fd` is an open descriptor
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(fds)
+}
+
+/// Reads `controller` until every copy of its terminal is closed.
+///
+/// Linux reports the hangup as `EIO` rather than end of file.
+fn read_until_hangup(mut controller: File) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    match controller.read_to_end(&mut output) {
+        Err(e) if e.raw_os_error() != Some(libc::EIO) => Err(e),
+        _ => Ok(output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+    use crate::verify_binary;
+
+    #[test]
+    fn verify_binary_pty_runs_the_tool_on_a_terminal() {
+        let dir = TempDir::new();
+        let tool = write_script(
+            &dir.join("tool"),
+            "if [ -t 1 ]; then\necho 'tool 1.0.0 (tty)'\necho 'built today'\nelse\necho 'tool 1.0.0'\nfi",
+        );
+        assert_eq!(
+            verify_binary_pty(&tool, "--version").unwrap(),
+            "tool 1.0.0 (tty)\nbuilt today"
+        );
+        assert_eq!(verify_binary(&tool).unwrap(), "tool 1.0.0");
+
+        let failing = write_script(&dir.join("failing"), "exit 2");
+        assert!(matches!(
+            verify_binary_pty(&failing, "--version"),
+            Err(ExecutableVerificationError::VersionCallFail(status)) if status.code() == Some(2)
+        ));
+    }
+}
@@ -0,0 +1,397 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{ExecutableBin, ExecutableVerificationError};
+
+/// Quotes `s` for POSIX `sh` using single quotes.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Renders a standalone `sh` script that recreates a link plan with `ln -sfn`.
+///
+/// Paths are single-quoted and follow a `--`, so none is split or taken for an
+/// option, whatever characters it contains.
+///
+/// # Arguments
+/// * `plan` - `(source, link)` pairs, in the order the links should be created
+pub fn to_install_script(plan: &[(PathBuf, PathBuf)]) -> String {
+    let mut script = String::from(
+        "#!/bin/sh\n# Generated by symlistow: recreates the planned symlinks.\nset -e\n",
+    );
+    for (source, link) in plan {
+        script.push_str(&format!(
+            "ln -sfn -- {} {}\n",
+            shell_quote(&source.to_string_lossy()),
+            shell_quote(&link.to_string_lossy())
+        ));
+    }
+    script
+}
+
+/// Renders `bins` as a plain-text table with `NAME`, `VERSION` and `PATH` columns.
+///
+/// Columns are padded to their widest cell. The version is the parsed semver
+/// version, or the first line of the version report when none parses.
+///
+/// # Arguments
+/// * `bins` - The rows, in order
+/// * `max_path_width` - If set, longer paths keep their end and are prefixed with `…`
+pub fn render_table(bins: &[ExecutableBin], max_path_width: Option<usize>) -> String {
+    let mut rows = vec![[
+        "NAME".to_string(),
+        "VERSION".to_string(),
+        "PATH".to_string(),
+    ]];
+    rows.extend(bins.iter().map(|bin| {
+        let version = display_version(bin);
+        let path = bin.path().to_string_lossy();
+        let path = match max_path_width {
+            Some(max) => truncate_start(&path, max),
+            None => path.into_owned(),
+        };
+        [bin.name().into_owned(), version, path]
+    }));
+
+    let width = |column: usize| {
+        rows.iter()
+            .map(|row| row[column].chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let (name_width, version_width) = (width(0), width(1));
+    let mut table = String::new();
+    for [name, version, path] in &rows {
+        table.push_str(&format!(
+            "{name:<name_width$}  {version:<version_width$}  {path}\n"
+        ));
+    }
+    table
+}
+
+/// The parsed semver version of `bin`, or the first line of its version report
+/// when none parses.
+fn display_version(bin: &ExecutableBin) -> String {
+    bin.version().map(|v| v.to_string()).unwrap_or_else(|_| {
+        let report = bin.version_report();
+        report.lines().next().unwrap_or(report).to_string()
+    })
+}
+
+/// Renders `bins` for node_exporter's textfile collector.
+///
+/// Each bin becomes a `symlistow_tool_info{name="...",version="..."} 1` sample
+/// of an info-style gauge, under one `# HELP` and `# TYPE` header. The version
+/// label is the same as [`render_table`]'s column; label values have `\`, `"`
+/// and newlines escaped as the exposition format requires.
+pub fn to_prometheus_textfile(bins: &[ExecutableBin]) -> String {
+    let mut text = String::from(
+        "# HELP symlistow_tool_info A verified tool and its version.\n\
+         # TYPE symlistow_tool_info gauge\n",
+    );
+    for bin in bins {
+        text.push_str(&format!(
+            "symlistow_tool_info{{name=\"{}\",version=\"{}\"}} 1\n",
+            escape_label(&bin.name()),
+            escape_label(&display_version(bin))
+        ));
+    }
+    text
+}
+
+/// Escapes `value` for use inside a quoted Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Renders `bins` as a Markdown catalog page with a `Name`, `Version`, `Path` and
+/// `Description` table.
+///
+/// The description is the first line of each tool's `--help`, captured with
+/// [`ExecutableBin::capture_help`], so every bin is run once more; it is left
+/// empty for tools whose help can't be captured. The version is the same as
+/// [`render_table`]'s column. Pipes and newlines in cells are escaped so each
+/// row stays one table row.
+pub fn to_markdown_catalog(bins: &[ExecutableBin]) -> String {
+    let mut catalog = String::from(
+        "# Tool catalog\n\n| Name | Version | Path | Description |\n| --- | --- | --- | --- |\n",
+    );
+    for bin in bins {
+        let help = bin.capture_help().unwrap_or_default();
+        let description = help.lines().map(str::trim).find(|line| !line.is_empty());
+        catalog.push_str(&format!(
+            "| {} | {} | `{}` | {} |\n",
+            escape_cell(&bin.name()),
+            escape_cell(&display_version(bin)),
+            escape_cell(&bin.path().to_string_lossy()).replace('`', "'"),
+            escape_cell(description.unwrap_or_default())
+        ));
+    }
+    catalog
+}
+
+/// Escapes `s` for a Markdown table cell.
+fn escape_cell(s: &str) -> String {
+    s.replace('|', r"\|").replace('\n', " ")
+}
+
+/// Renders verification failures as GitHub Actions `::error` workflow commands.
+///
+/// Each failure becomes a `::error file=<path>::<message>` line, which Actions
+/// shows as an annotation on the file. The path and message are escaped as
+/// workflow commands require, so messages spanning lines stay one command.
+pub fn to_github_annotations(failures: &[(PathBuf, ExecutableVerificationError)]) -> String {
+    failures
+        .iter()
+        .map(|(path, error)| github_command("error", path, &error.to_string()))
+        .collect()
+}
+
+/// Renders successful verifications as GitHub Actions `::notice` workflow
+/// commands, one `::notice file=<path>::<name> <version>` line per bin.
+///
+/// The version is the same as [`render_table`]'s column.
+pub fn to_github_notices(bins: &[ExecutableBin]) -> String {
+    bins.iter()
+        .map(|bin| {
+            let message = format!("{} {}", bin.name(), display_version(bin));
+            github_command("notice", bin.path(), &message)
+        })
+        .collect()
+}
+
+/// One `::<command> file=<path>::<message>` line.
+fn github_command(command: &str, path: &Path, message: &str) -> String {
+    let file = escape_github_data(&path.to_string_lossy())
+        .replace(':', "%3A")
+        .replace(',', "%2C");
+    format!("::{command} file={file}::{}\n", escape_github_data(message))
+}
+
+/// Escapes `s` for use in a workflow command's message.
+fn escape_github_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// The last `max - 1` characters of `s` behind an ellipsis, if `s` is longer than `max`.
+fn truncate_start(s: &str, max: usize) -> String {
+    let len = s.chars().count();
+    if len <= max {
+        return s.to_string();
+    }
+    let keep = max.saturating_sub(1);
+    std::iter::once('…')
+        .chain(s.chars().skip(len - keep))
+        .collect()
+}
+
+impl ExecutableBin {
+    /// Writes a one-line provenance record of this verification to `out`.
+    ///
+    /// The record is a series of `key="value"` pairs: `path`, `version`, `sha256`
+    /// when `checksum` is set, `timestamp` in seconds since the Unix epoch, and
+    /// `user` from `$USER`. Values are quoted and escaped, so each record stays on
+    /// one line and records can be appended to a single audit log.
+    ///
+    /// # Arguments
+    /// * `out` - Where the record is written
+    /// * `checksum` - Whether to hash the binary, which reads the whole file
+    pub fn write_provenance(&self, out: &mut impl Write, checksum: bool) -> io::Result<()> {
+        write!(
+            out,
+            "path={:?} version={:?}",
+            self.path().to_string_lossy(),
+            self.version_report()
+        )?;
+        if checksum {
+            write!(out, " sha256={:?}", self.sha256()?)?;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let user = env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+        writeln!(out, " timestamp=\"{timestamp}\" user={user:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn to_install_script_quotes_paths() {
+        let plan = [
+            (
+                PathBuf::from("/opt/my tools/bin/tool"),
+                PathBuf::from("/home/me/.local/bin/my tool"),
+            ),
+            (PathBuf::from("-rf x"), PathBuf::from("/tmp/it's")),
+        ];
+        let script = to_install_script(&plan);
+        let lines = script.lines().skip(3).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "ln -sfn -- '/opt/my tools/bin/tool' '/home/me/.local/bin/my tool'",
+                r"ln -sfn -- '-rf x' '/tmp/it'\''s'",
+            ]
+        );
+    }
+
+    #[test]
+    fn to_install_script_recreates_the_links() {
+        let dir = TempDir::new();
+        let plan = [
+            (dir.join("my tool"), dir.join("link one")),
+            (PathBuf::from("-n"), dir.join("it's")),
+        ];
+        let script_path = dir.join("install.sh");
+        fs::write(&script_path, to_install_script(&plan)).unwrap();
+        let status = Command::new("sh").arg(&script_path).status().unwrap();
+        assert!(status.success());
+        for (source, link) in &plan {
+            assert_eq!(fs::read_link(link).unwrap(), *source);
+        }
+    }
+
+    #[test]
+    fn write_provenance_records_path_and_version_on_one_line() {
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("tool"), r#"echo 'tool 1.0.0 "stable"'"#);
+        let bin = ExecutableBin::new(&tool).unwrap();
+        let mut record = Vec::new();
+        bin.write_provenance(&mut record, true).unwrap();
+        let record = String::from_utf8(record).unwrap();
+
+        assert_eq!(record.lines().count(), 1);
+        assert!(record.ends_with('\n'));
+        assert!(record.starts_with(&format!(
+            r#"path="{}" version="tool 1.0.0 \"stable\"" sha256="{}""#,
+            tool.display(),
+            bin.sha256().unwrap()
+        )));
+        assert!(record.contains(" timestamp=\""));
+    }
+
+    #[test]
+    fn render_table_aligns_columns_under_a_header() {
+        let bins = [
+            ExecutableBin::new_unverified("/usr/bin/git".into(), "git version 2.43.0".into()),
+            ExecutableBin::new_unverified(
+                "/home/me/.cargo/bin/cargo-nextest".into(),
+                "cargo-nextest 0.9.67\nrelease: 0.9.67".into(),
+            ),
+            ExecutableBin::new_unverified("/opt/x".into(), "x (unreleased)\nmore".into()),
+        ];
+        assert_eq!(
+            render_table(&bins, None),
+            "\
+NAME           VERSION         PATH
+git            2.43.0          /usr/bin/git
+cargo-nextest  0.9.67          /home/me/.cargo/bin/cargo-nextest
+x              x (unreleased)  /opt/x
+"
+        );
+
+        let truncated = render_table(&bins, Some(12));
+        let paths = truncated
+            .lines()
+            .map(|line| line.rsplit("  ").next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["PATH", "/usr/bin/git", "…rgo-nextest", "/opt/x"]);
+    }
+
+    #[test]
+    fn to_prometheus_textfile_escapes_label_values() {
+        let bins = [
+            ExecutableBin::new_unverified("/usr/bin/git".into(), "git version 2.43.0".into()),
+            ExecutableBin::new_unverified(
+                "/opt/bin/we\"ird\\to\nol".into(),
+                "custom \"nightly\"\nbuilt today".into(),
+            ),
+        ];
+        assert_eq!(
+            to_prometheus_textfile(&bins),
+            concat!(
+                "# HELP symlistow_tool_info A verified tool and its version.\n",
+                "# TYPE symlistow_tool_info gauge\n",
+                "symlistow_tool_info{name=\"git\",version=\"2.43.0\"} 1\n",
+                r#"symlistow_tool_info{name="we\"ird\\to\nol",version="custom \"nightly\""} 1"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn to_github_annotations_escapes_the_file_and_message() {
+        let missing = |path: &str| {
+            (
+                PathBuf::from(path),
+                ExecutableVerificationError::MissingPath(path.into()),
+            )
+        };
+        let failures = [
+            missing("/opt/my,tools/bin/50%:tool"),
+            missing("/tmp/two\nlines"),
+        ];
+        assert_eq!(
+            to_github_annotations(&failures),
+            concat!(
+                "::error file=/opt/my%2Ctools/bin/50%25%3Atool::",
+                "Path doesn't exist: /opt/my,tools/bin/50%25:tool\n",
+                "::error file=/tmp/two%0Alines::Path doesn't exist: /tmp/two%0Alines\n",
+            )
+        );
+        assert_eq!(to_github_annotations(&[]), "");
+    }
+
+    #[test]
+    fn to_markdown_catalog_has_a_row_per_tool() {
+        let dir = TempDir::new();
+        let jq = write_script(
+            &dir.join("jq"),
+            "case \"$1\" in\n--help) printf '\\n  Usage: jq FILTER | more\\nOptions:\\n' ;;\n*) echo jq-1.7.1 ;;\nesac",
+        );
+        let quiet = write_script(
+            &dir.join("quiet"),
+            "[ \"$1\" = --help ] || echo 'quiet 2.0.0'",
+        );
+        let bins = [
+            ExecutableBin::new(&jq).unwrap(),
+            ExecutableBin::new(&quiet).unwrap(),
+            ExecutableBin::new_unverified(dir.join("gone"), "gone (unknown)\nextra".into()),
+        ];
+
+        let catalog = to_markdown_catalog(&bins);
+        let lines = catalog.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "# Tool catalog".to_string(),
+                String::new(),
+                "| Name | Version | Path | Description |".to_string(),
+                "| --- | --- | --- | --- |".to_string(),
+                format!(
+                    "| jq | 1.7.1 | `{}` | Usage: jq FILTER \\| more |",
+                    jq.display()
+                ),
+                format!("| quiet | 2.0.0 | `{}` |  |", quiet.display()),
+                format!(
+                    "| gone | gone (unknown) | `{}` |  |",
+                    dir.join("gone").display()
+                ),
+            ]
+        );
+    }
+}
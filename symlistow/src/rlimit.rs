@@ -0,0 +1,108 @@
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+
+/// Resource limits applied to a version probe with `setrlimit` before it execs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResourceLimits {
+    /// CPU time in seconds. The probe gets `SIGXCPU` at the limit and is killed
+    /// a second later.
+    pub cpu_seconds: Option<u64>,
+    /// Virtual address space in bytes; allocations beyond it fail.
+    pub address_space_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Makes `command` set these limits in the child between fork and exec.
+    pub(crate) fn apply(self, command: &mut Command) {
+        // SAFETY: the hook only calls setrlimit, which is async-signal-safe, and
+        // touches no memory shared with the parent
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(seconds) = self.cpu_seconds {
+                    set_limit(libc::RLIMIT_CPU, seconds, seconds.saturating_add(1))?;
+                }
+                if let Some(bytes) = self.address_space_bytes {
+                    set_limit(libc::RLIMIT_AS, bytes, bytes)?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(target_env = "gnu")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type Resource = libc::c_int;
+
+fn set_limit(resource: Resource, soft: u64, hard: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    // SAFETY: `limit` is a valid rlimit for the duration of the call
+    if unsafe { libc::setrlimit(resource, &limit) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Whether a failed probe run under `limits` looks like it was stopped by them.
+///
+/// Exhausting CPU time ends in `SIGXCPU` or `SIGKILL`, and failed allocations
+/// usually end in an abort or a segfault, so any death by signal counts. A tool
+/// that handles allocation failure and exits normally can't be told apart from
+/// any other failure.
+pub(crate) fn hit_limit(limits: Option<ResourceLimits>, status: ExitStatus) -> bool {
+    limits.is_some() && status.signal().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+    use crate::{ExecutableVerificationError, VerifyOptions, verify_binary_with};
+
+    fn limited(limits: ResourceLimits) -> VerifyOptions {
+        VerifyOptions {
+            resource_limits: Some(limits),
+            ..VerifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn probes_past_their_limits_fail_with_resource_limit_exceeded() {
+        let dir = TempDir::new();
+        let hungry = write_script(
+            &dir.join("hungry"),
+            r#"x=$(head -c 100000000 /dev/zero | tr '\0' x)
+echo "hungry ${#x}""#,
+        );
+        let spinning = write_script(&dir.join("spinning"), "while :; do :; done");
+        let modest = write_script(&dir.join("modest"), "echo 'modest 1.0.0'");
+        let memory = limited(ResourceLimits {
+            address_space_bytes: Some(50 << 20),
+            ..ResourceLimits::default()
+        });
+        let cpu = limited(ResourceLimits {
+            cpu_seconds: Some(1),
+            ..ResourceLimits::default()
+        });
+
+        for (tool, options) in [(&hungry, &memory), (&spinning, &cpu)] {
+            match verify_binary_with(tool, options) {
+                Err(ExecutableVerificationError::ResourceLimitExceeded(status)) => {
+                    assert!(status.signal().is_some(), "{status}");
+                }
+                other => panic!("expected ResourceLimitExceeded, got {other:?}"),
+            }
+        }
+        assert_eq!(
+            verify_binary_with(&modest, &memory).unwrap(),
+            "modest 1.0.0"
+        );
+        assert_eq!(verify_binary_with(&modest, &cpu).unwrap(), "modest 1.0.0");
+    }
+}
@@ -0,0 +1,340 @@
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Runs external commands on behalf of the crate.
+///
+/// Functions that shell out to helper tools take a runner so callers can
+/// substitute or wrap how those commands execute.
+pub trait CommandRunner {
+    /// Runs `command` to completion and captures its output.
+    fn output(&self, command: &mut Command) -> io::Result<Output>;
+
+    /// Like [`CommandRunner::output`], but kills `command` with an
+    /// [`io::ErrorKind::TimedOut`] error if it runs longer than `timeout`.
+    ///
+    /// By default `command` is run directly rather than through
+    /// [`CommandRunner::output`]; runners that change how commands run should
+    /// override this too.
+    fn output_within(&self, command: &mut Command, timeout: Duration) -> io::Result<Output> {
+        output_with_timeout(command, timeout)
+    }
+
+    /// Like [`CommandRunner::output_within`], with an optional timeout, but
+    /// kills `command` with an [`io::ErrorKind::FileTooLarge`] error once it
+    /// writes more than `max_stdout` bytes to stdout, so a runaway command can't
+    /// exhaust memory. At most `max_stdout` bytes of stderr are kept.
+    ///
+    /// The same default and advice as for [`CommandRunner::output_within`] apply.
+    fn output_capped(
+        &self,
+        command: &mut Command,
+        max_stdout: u64,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        output_with_cap(command, max_stdout, timeout)
+    }
+}
+
+/// Runs commands directly with [`Command::output`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, command: &mut Command) -> io::Result<Output> {
+        command.output()
+    }
+}
+
+/// Runs `.wasm` modules through a WebAssembly runtime.
+///
+/// A command whose program ends in `.wasm` is run as `<runtime> run <module>
+/// <args>...`, keeping its environment and working directory; any other command
+/// runs directly. Stdio configuration isn't carried over to the rewritten command.
+#[derive(Debug, Clone)]
+pub struct WasmRunner {
+    runtime: PathBuf,
+}
+
+impl WasmRunner {
+    /// A runner using `wasmtime` from `$PATH`.
+    pub fn new() -> Self {
+        Self::with_runtime("wasmtime")
+    }
+
+    /// A runner using the runtime at `runtime`, which must accept `run <module>`.
+    pub fn with_runtime(runtime: impl Into<PathBuf>) -> Self {
+        Self {
+            runtime: runtime.into(),
+        }
+    }
+}
+
+impl Default for WasmRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmRunner {
+    /// The runtime invocation running `command`, if its program is a `.wasm` module.
+    fn rewrite(&self, command: &Command) -> Option<Command> {
+        let module = Path::new(command.get_program());
+        if module.extension().is_none_or(|ext| ext != "wasm") {
+            return None;
+        }
+        let mut wasm = Command::new(&self.runtime);
+        wasm.arg("run").arg(module).args(command.get_args());
+        for (key, value) in command.get_envs() {
+            match value {
+                Some(value) => wasm.env(key, value),
+                None => wasm.env_remove(key),
+            };
+        }
+        if let Some(dir) = command.get_current_dir() {
+            wasm.current_dir(dir);
+        }
+        Some(wasm)
+    }
+}
+
+impl CommandRunner for WasmRunner {
+    fn output(&self, command: &mut Command) -> io::Result<Output> {
+        match self.rewrite(command) {
+            Some(mut wasm) => wasm.output(),
+            None => command.output(),
+        }
+    }
+
+    fn output_within(&self, command: &mut Command, timeout: Duration) -> io::Result<Output> {
+        match self.rewrite(command) {
+            Some(mut wasm) => output_with_timeout(&mut wasm, timeout),
+            None => output_with_timeout(command, timeout),
+        }
+    }
+
+    fn output_capped(
+        &self,
+        command: &mut Command,
+        max_stdout: u64,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        match self.rewrite(command) {
+            Some(mut wasm) => output_with_cap(&mut wasm, max_stdout, timeout),
+            None => output_with_cap(command, max_stdout, timeout),
+        }
+    }
+}
+
+/// Runs `command` with stdin closed, killing it if it runs longer than `timeout`.
+///
+/// Output is drained on background threads so a chatty child can't block on a
+/// full pipe while we wait for it.
+pub(crate) fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    output_with_input(command, None, timeout)
+}
+
+/// Like [`output_with_timeout`], but feeds `input` to the command's stdin, if set.
+///
+/// The input is written on a background thread, so a child that doesn't read
+/// all of it, or writes before reading, can't deadlock us.
+pub(crate) fn output_with_input(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    timeout: Duration,
+) -> io::Result<Output> {
+    run_captured(command, input, Some(timeout), None)
+}
+
+/// Runs `command` with stdin closed, killing it with an
+/// [`io::ErrorKind::FileTooLarge`] error once it writes more than `max_stdout`
+/// bytes to stdout, or with an [`io::ErrorKind::TimedOut`] error past `timeout`.
+///
+/// At most `max_stdout` bytes of stderr are kept; the rest is discarded.
+pub(crate) fn output_with_cap(
+    command: &mut Command,
+    max_stdout: u64,
+    timeout: Option<Duration>,
+) -> io::Result<Output> {
+    run_captured(command, None, timeout, Some(max_stdout))
+}
+
+/// Runs `command` in a new process group, so that on timeout or overflow the
+/// whole group is killed, including any background process it left behind.
+///
+/// Such a process can hold the output pipes open after `command` exits, so the
+/// deadline and the stdout cap keep applying until both pipes are drained.
+fn run_captured(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    timeout: Option<Duration>,
+    max_output: Option<u64>,
+) -> io::Result<Output> {
+    let stdin = if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let mut child = command
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+    if let (Some(mut pipe), Some(input)) = (child.stdin.take(), input) {
+        let input = input.to_vec();
+        // A child exiting without reading its input is not our error
+        thread::spawn(move || {
+            let _ = pipe.write_all(&input);
+        });
+    }
+    let overflowed = Arc::new(AtomicBool::new(false));
+    let stdout = drain(child.stdout.take(), max_output, Some(overflowed.clone()));
+    let stderr = drain(child.stderr.take(), max_output, None);
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut status = None;
+    loop {
+        if status.is_none() {
+            status = child.try_wait()?;
+        }
+        if status.is_some() && stdout.is_finished() && stderr.is_finished() {
+            break;
+        }
+        if overflowed.load(Ordering::Relaxed) {
+            kill_group(&mut child);
+            return Err(output_too_large(max_output));
+        }
+        if let (Some(deadline), Some(timeout)) = (deadline, timeout)
+            && Instant::now() >= deadline
+        {
+            kill_group(&mut child);
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("command did not finish within {timeout:?}"),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    // The child may have exited right after writing too much
+    if overflowed.load(Ordering::Relaxed) {
+        return Err(output_too_large(max_output));
+    }
+    Ok(Output {
+        status: status.expect("loop exits only once the child has exited"),
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Kills the process group led by `child`, then reaps `child` if it hasn't been.
+///
+/// The group outlives a reaped leader while any member is alive, and its id
+/// isn't reused meanwhile, so this is safe after `child` has exited.
+fn kill_group(child: &mut Child) {
+    if let Ok(pgid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: killpg takes no pointers
+        unsafe { libc::killpg(pgid, libc::SIGKILL) };
+    }
+    let _ = child.wait();
+}
+
+fn output_too_large(max_output: Option<u64>) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::FileTooLarge,
+        format!(
+            "command wrote more than {} bytes to stdout",
+            max_output.unwrap_or_default()
+        ),
+    )
+}
+
+/// Reads `pipe` to the end on a background thread, keeping at most `max` bytes.
+///
+/// With `overflowed`, reading stops as soon as more than `max` bytes arrive and
+/// the flag is set; without it, bytes past `max` are read and discarded so the
+/// writer never blocks.
+fn drain<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    max: Option<u64>,
+    overflowed: Option<Arc<AtomicBool>>,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let Some(mut pipe) = pipe else {
+            return buf;
+        };
+        let Some(max) = max else {
+            let _ = pipe.read_to_end(&mut buf);
+            return buf;
+        };
+        match overflowed {
+            Some(overflowed) => {
+                let _ = (&mut pipe)
+                    .take(max.saturating_add(1))
+                    .read_to_end(&mut buf);
+                if buf.len() as u64 > max {
+                    overflowed.store(true, Ordering::Relaxed);
+                }
+            }
+            None => {
+                let _ = (&mut pipe).take(max).read_to_end(&mut buf);
+                let _ = io::copy(&mut pipe, &mut io::sink());
+            }
+        }
+        buf
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+    use crate::{ExecutableBin, VerifyOptions};
+
+    #[test]
+    fn wasm_runner_runs_modules_through_the_runtime() {
+        let dir = TempDir::new();
+        let runtime = write_script(&dir.join("wasmtime"), r#"printf '%s\n' "$@""#);
+        let module = dir.join("tool.wasm");
+        fs::write(&module, b"\0asm\x01\0\0\0").unwrap();
+        let runner = WasmRunner::with_runtime(&runtime);
+
+        let expected = format!("run\n{}\n--version", module.display());
+        let bin =
+            ExecutableBin::new_with_runner(&module, &VerifyOptions::default(), &runner).unwrap();
+        assert_eq!(bin.path(), module);
+        assert_eq!(bin.version_report(), expected);
+        let options = VerifyOptions {
+            timeout: Some(Duration::from_secs(5)),
+            ..VerifyOptions::default()
+        };
+        let bin = ExecutableBin::new_with_runner(&module, &options, &runner).unwrap();
+        assert_eq!(bin.version_report(), expected);
+
+        // Anything else runs directly
+        let native = write_script(&dir.join("native"), "echo 'native 1.0.0'");
+        let bin =
+            ExecutableBin::new_with_runner(&native, &VerifyOptions::default(), &runner).unwrap();
+        assert_eq!(bin.version_report(), "native 1.0.0");
+    }
+
+    #[test]
+    fn a_background_process_holding_the_pipes_cannot_outlast_the_timeout() {
+        let dir = TempDir::new();
+        let tool = write_script(&dir.join("tool"), "sleep 5 &\necho 'tool 1.0.0'");
+
+        let start = Instant::now();
+        let error =
+            output_with_timeout(&mut Command::new(&tool), Duration::from_millis(500)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+}
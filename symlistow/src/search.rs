@@ -0,0 +1,528 @@
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::{ExecutableBin, ExecutableVerificationError, verify_binary};
+
+/// Whether `path` is a regular file (after following links) with an execute bit set.
+pub(crate) fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// Finds the first executable named `name` in the directories of `$PATH`.
+///
+/// A `name` containing a `/` is treated as a path and not searched for.
+pub fn find_on_path(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+    let path_var = env::var_os("PATH")?;
+    env::split_paths(&path_var)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// The first executable named `name` on `$PATH`, as `which` would print it.
+pub fn which_line(name: &str) -> Option<String> {
+    find_on_path(name).map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Every executable named `name` on `$PATH`, in `$PATH` order, like `which -a`.
+///
+/// The first entry is the one a shell would run.
+pub fn which_all(name: &str) -> Vec<PathBuf> {
+    if name.contains('/') {
+        return find_on_path(name).into_iter().collect();
+    }
+    let Some(path_var) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    env::split_paths(&path_var)
+        .map(|dir| dir.join(name))
+        .filter(|candidate| is_executable(candidate))
+        .collect()
+}
+
+/// Verifies every copy of `name` on `$PATH`, in `$PATH` order.
+///
+/// The first entry is the copy that actually runs; the rest are shadowed by it.
+pub fn all_versions_on_path(
+    name: &str,
+) -> Vec<(PathBuf, Result<String, ExecutableVerificationError>)> {
+    which_all(name)
+        .into_iter()
+        .map(|path| {
+            let version = verify_binary(&path);
+            (path, version)
+        })
+        .collect()
+}
+
+/// Copies of one tool on `$PATH` that report different versions.
+#[derive(Debug, Clone)]
+pub struct SkewReport {
+    /// Every copy that verified, in `$PATH` order.
+    pub copies: Vec<ExecutableBin>,
+    /// The copy a shell would run.
+    pub winner: PathBuf,
+}
+
+/// Verifies every copy of `name` on `$PATH` and reports them if their versions differ.
+///
+/// Versions are compared as semver where the reports parse, and as raw reports
+/// otherwise. Copies that fail verification are left out of the comparison.
+///
+/// # Returns
+/// * `None` if there are fewer than two copies or they all agree
+/// * `Some` report listing each verified copy and the one that wins
+pub fn detect_path_skew(name: &str) -> Option<SkewReport> {
+    let paths = which_all(name);
+    let winner = paths.first()?.clone();
+    let copies = paths
+        .into_iter()
+        .filter_map(|path| ExecutableBin::new(path).ok())
+        .collect::<Vec<_>>();
+    let versions = copies
+        .iter()
+        .map(|bin| {
+            bin.version()
+                .map(|version| version.to_string())
+                .unwrap_or_else(|_| bin.version_report().to_string())
+        })
+        .collect::<HashSet<_>>();
+    (versions.len() > 1).then_some(SkewReport { copies, winner })
+}
+
+/// Which copy of a tool on `$PATH` wins, and how to make the newest one win.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowResolution {
+    /// The copy a shell would run.
+    pub winner: PathBuf,
+    /// The later copies it shadows, in `$PATH` order.
+    pub shadowed: Vec<PathBuf>,
+    /// Whether no copy reports a newer version than the winner, or `None` if the
+    /// winner's version, or that of every other copy, doesn't parse.
+    pub winner_is_newest: Option<bool>,
+    /// When the winner isn't the newest, a `$PATH` with the newest copy's entry
+    /// moved just ahead of the winner's, so that the newest wins.
+    pub suggested_path: Option<OsString>,
+}
+
+/// Verifies every copy of `name` on `$PATH` and tells whether the one that wins
+/// is the newest.
+///
+/// Versions are compared as semver; copies whose report doesn't parse, or that
+/// fail verification, are never the newest.
+///
+/// # Returns
+/// * `None` if `name` isn't on `$PATH`
+pub fn shadow_resolution(name: &str) -> Option<ShadowResolution> {
+    let dirs = env::split_paths(&env::var_os("PATH")?).collect::<Vec<_>>();
+    // Each copy, with the index of the `$PATH` entry it was found in
+    let copies = dirs
+        .iter()
+        .enumerate()
+        .map(|(index, dir)| (index, dir.join(name)))
+        .filter(|(_, candidate)| is_executable(candidate))
+        .collect::<Vec<_>>();
+    let ((winner_index, winner), rest) = copies.split_first()?;
+
+    let version = |path: &PathBuf| ExecutableBin::new(path).ok()?.version().ok();
+    let winner_version = version(winner);
+    let newest = rest
+        .iter()
+        .filter_map(|(index, path)| Some((*index, version(path)?)))
+        .max_by(|(_, a), (_, b)| a.cmp(b));
+    let winner_is_newest = match (&winner_version, &newest) {
+        (Some(winner), Some((_, newest))) => Some(winner >= newest),
+        _ => None,
+    };
+    let suggested_path = match newest {
+        Some((newest_index, _)) if winner_is_newest == Some(false) => {
+            let mut reordered = dirs.clone();
+            let entry = reordered.remove(newest_index);
+            reordered.insert(*winner_index, entry);
+            env::join_paths(reordered).ok()
+        }
+        _ => None,
+    };
+
+    Some(ShadowResolution {
+        winner: winner.clone(),
+        shadowed: rest.iter().map(|(_, path)| path.clone()).collect(),
+        winner_is_newest,
+        suggested_path,
+    })
+}
+
+/// Finds `name` on `$PATH` and verifies it.
+pub fn verify_named(name: &str) -> Result<ExecutableBin, ExecutableVerificationError> {
+    let path = find_on_path(name)
+        .ok_or_else(|| ExecutableVerificationError::NotOnPath(name.to_string()))?;
+    ExecutableBin::new(path)
+}
+
+/// Lazily verifies the executables directly inside `dir` whose metadata passes `pred`.
+///
+/// `pred` sees the metadata of each executable after following links, and only
+/// the entries it accepts are run. Entries come in directory order, and a
+/// directory that can't be read yields a single error.
+pub fn scan_dir_filtered(
+    dir: &Path,
+    pred: impl Fn(&fs::Metadata) -> bool,
+) -> impl Iterator<Item = Result<ExecutableBin, ExecutableVerificationError>> {
+    let (entries, error) = match fs::read_dir(dir) {
+        Ok(entries) => (Some(entries), None),
+        Err(e) => (None, Some(e)),
+    };
+    let verified = entries.into_iter().flatten().filter_map(move |entry| {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => return Some(Err(e.into())),
+        };
+        let metadata = fs::metadata(&path).ok()?;
+        let executable = metadata.is_file() && metadata.permissions().mode() & 0o111 != 0;
+        (executable && pred(&metadata)).then(|| ExecutableBin::new(path))
+    });
+    error.map(|e| Err(e.into())).into_iter().chain(verified)
+}
+
+/// The conventional install directories: `/usr/bin`, `/usr/local/bin`, and
+/// `~/.local/bin` and `~/.cargo/bin` when `$HOME` is set.
+pub fn standard_locations() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/usr/bin"), PathBuf::from("/usr/local/bin")];
+    if let Some(home) = env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        dirs.extend([home.join(".local/bin"), home.join(".cargo/bin")]);
+    }
+    dirs
+}
+
+/// Verifies every executable in the [`standard_locations`].
+pub fn scan_standard_locations() -> Vec<ExecutableBin> {
+    scan_locations(&standard_locations())
+}
+
+/// Verifies every executable directly inside `dirs`, in the order of `dirs`.
+///
+/// Executables that resolve to a file already found, e.g. through a symlink from
+/// one directory into another, are only verified once, under the first path they
+/// were found at. Missing directories and executables that fail verification
+/// are skipped.
+pub fn scan_locations(dirs: &[PathBuf]) -> Vec<ExecutableBin> {
+    let mut seen = HashSet::new();
+    let mut bins = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let Ok(resolved) = fs::canonicalize(&path) else {
+                continue;
+            };
+            if is_executable(&resolved) && seen.insert(resolved) {
+                bins.extend(ExecutableBin::new(path).ok());
+            }
+        }
+    }
+    bins
+}
+
+/// Removes duplicate, nonexistent and executable-free directories from a
+/// `PATH`-style string.
+///
+/// Surviving entries keep the order of their first occurrence.
+pub fn prune_path(path_var: &str) -> String {
+    let mut seen = HashSet::new();
+    path_var
+        .split(':')
+        .filter(|dir| seen.insert(*dir))
+        .filter(|dir| contains_executable(Path::new(dir)))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Maps each executable basename found in more than one of `dirs` to every
+/// location it appears at.
+///
+/// Locations are listed in the order of `dirs`, so the first is the one that
+/// would win were `dirs` used as a `PATH`. A directory listed more than once is
+/// only searched the first time, and unreadable directories are skipped. Nothing
+/// is run.
+pub fn find_basename_conflicts(dirs: &[PathBuf]) -> BTreeMap<String, Vec<PathBuf>> {
+    let mut seen = HashSet::new();
+    let mut locations = BTreeMap::<String, Vec<PathBuf>>::new();
+    for dir in dirs.iter().filter(|dir| seen.insert(*dir)) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if is_executable(&path) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                locations.entry(name).or_default().push(path);
+            }
+        }
+    }
+    locations.retain(|_, paths| paths.len() > 1);
+    locations
+}
+
+fn contains_executable(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .filter_map(Result::ok)
+            .any(|entry| is_executable(&entry.path()))
+    })
+}
+
+/// Finds and verifies each named tool on `$PATH`.
+///
+/// Expands to a `Vec<Result<ExecutableBin, ExecutableVerificationError>>` in the
+/// order the names were given, e.g. `verify_tools!["git", "cargo", "rustc"]`.
+#[macro_export]
+macro_rules! verify_tools {
+    ($($name:literal),* $(,)?) => {
+        vec![$($crate::verify_named($name)),*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempDir, with_path_prefix, write_script};
+
+    #[test]
+    fn verify_tools_verifies_each_name_in_order() {
+        let results = crate::verify_tools!["ls", "cat", "symlistow-missing-tool",];
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().name(), "ls");
+        assert_eq!(results[1].as_ref().unwrap().name(), "cat");
+        assert!(matches!(
+            &results[2],
+            Err(ExecutableVerificationError::NotOnPath(name)) if name == "symlistow-missing-tool"
+        ));
+    }
+
+    #[test]
+    fn prune_path_drops_duplicate_missing_and_empty_dirs() {
+        let dir = TempDir::new();
+        let (tools, more, empty) = (dir.join("tools"), dir.join("more"), dir.join("empty"));
+        for sub in [&tools, &more, &empty] {
+            fs::create_dir(sub).unwrap();
+        }
+        write_script(&tools.join("a"), "true");
+        write_script(&more.join("b"), "true");
+        fs::write(empty.join("data"), "not executable").unwrap();
+
+        let entries = [&tools, &dir.join("missing"), &empty, &tools, &more]
+            .map(|path| path.to_str().unwrap().to_string());
+        let pruned = prune_path(&entries.join(":"));
+        assert_eq!(pruned, format!("{}:{}", tools.display(), more.display()));
+    }
+
+    #[test]
+    fn which_finds_sh_but_not_a_missing_name() {
+        let first = which_line("sh").unwrap();
+        assert!(first.ends_with("/sh"), "{first}");
+        let all = which_all("sh");
+        assert_eq!(
+            all.first().map(|path| path.to_string_lossy()),
+            Some(first.into())
+        );
+        assert!(all.iter().all(|path| is_executable(path)));
+
+        assert_eq!(which_line("symlistow-missing-tool"), None);
+        assert!(which_all("symlistow-missing-tool").is_empty());
+        assert_eq!(which_all("/bin/sh"), [PathBuf::from("/bin/sh")]);
+    }
+
+    #[test]
+    fn all_versions_on_path_verifies_every_copy_in_path_order() {
+        let dir = TempDir::new();
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        for (sub, report) in [(&first, "skew 1.0.0"), (&second, "skew 2.0.0")] {
+            fs::create_dir(sub).unwrap();
+            write_script(&sub.join("symlistow-skew"), &format!("echo '{report}'"));
+        }
+
+        let versions = with_path_prefix(&[&first, &second], || {
+            all_versions_on_path("symlistow-skew")
+        });
+        let versions = versions
+            .into_iter()
+            .map(|(path, version)| (path, version.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            versions,
+            [
+                (first.join("symlistow-skew"), "skew 1.0.0".to_string()),
+                (second.join("symlistow-skew"), "skew 2.0.0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_path_skew_reports_copies_with_different_versions() {
+        let dir = TempDir::new();
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        for (sub, skewed, agreed) in [
+            (&first, "skewed 2.0.0", "agreed 1.0.0"),
+            (&second, "skewed 1.4.0", "Agreed 1.0.0"),
+        ] {
+            fs::create_dir(sub).unwrap();
+            write_script(&sub.join("symlistow-skewed"), &format!("echo '{skewed}'"));
+            write_script(&sub.join("symlistow-agreed"), &format!("echo '{agreed}'"));
+        }
+        write_script(&first.join("symlistow-single"), "echo 'single 1.0.0'");
+
+        let (skewed, agreed, single) = with_path_prefix(&[&first, &second], || {
+            (
+                detect_path_skew("symlistow-skewed"),
+                detect_path_skew("symlistow-agreed"),
+                detect_path_skew("symlistow-single"),
+            )
+        });
+        let skewed = skewed.unwrap();
+        assert_eq!(skewed.winner, first.join("symlistow-skewed"));
+        let copies = skewed
+            .copies
+            .iter()
+            .map(|bin| (bin.path(), bin.version_report()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            copies,
+            [
+                (first.join("symlistow-skewed").as_path(), "skewed 2.0.0"),
+                (second.join("symlistow-skewed").as_path(), "skewed 1.4.0"),
+            ]
+        );
+        // Reports that differ but parse to the same version aren't skew
+        assert!(agreed.is_none());
+        assert!(single.is_none());
+    }
+
+    #[test]
+    fn scan_dir_filtered_only_verifies_accepted_entries() {
+        let dir = TempDir::new();
+        let marker = dir.join("ran");
+        let padding = format!("# {}", "x".repeat(4096));
+        for (name, body) in [
+            ("big-one", format!("echo 'big-one 1.0.0'\n{padding}")),
+            ("big-two", format!("echo 'big-two 2.0.0'\n{padding}")),
+            ("small", format!("echo small >> '{}'", marker.display())),
+        ] {
+            write_script(&dir.join(name), &body);
+        }
+        fs::write(dir.join("big-data"), padding.repeat(2)).unwrap();
+
+        let mut reports = scan_dir_filtered(dir.path(), |metadata| metadata.len() > 4096)
+            .map(|bin| bin.unwrap().version_report().to_string())
+            .collect::<Vec<_>>();
+        reports.sort();
+        assert_eq!(reports, ["big-one 1.0.0", "big-two 2.0.0"]);
+        assert!(!marker.exists(), "the small script was run");
+
+        let missing_dir = dir.join("missing");
+        let mut missing = scan_dir_filtered(&missing_dir, |_| true);
+        assert!(matches!(
+            missing.next(),
+            Some(Err(ExecutableVerificationError::ExecutionError(_)))
+        ));
+        assert!(missing.next().is_none());
+    }
+
+    #[test]
+    fn find_basename_conflicts_lists_each_location_in_dir_order() {
+        let dir = TempDir::new();
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        fs::create_dir(&first).unwrap();
+        fs::create_dir(&second).unwrap();
+        write_script(&first.join("tool"), "echo 'tool 2.0.0'");
+        write_script(&second.join("tool"), "echo 'tool 1.0.0'");
+        write_script(&first.join("only-here"), "echo 'only-here 1.0.0'");
+        fs::write(first.join("notes"), "not executable").unwrap();
+        fs::write(second.join("notes"), "not executable").unwrap();
+
+        let dirs = [
+            second.clone(),
+            first.clone(),
+            second.clone(),
+            dir.join("missing"),
+        ];
+        let conflicts = find_basename_conflicts(&dirs);
+        assert_eq!(
+            conflicts,
+            BTreeMap::from([(
+                "tool".to_string(),
+                vec![second.join("tool"), first.join("tool")]
+            )])
+        );
+    }
+
+    #[test]
+    fn scan_locations_verifies_a_symlinked_binary_once() {
+        let dir = TempDir::new();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        fs::create_dir(&a).unwrap();
+        fs::create_dir(&b).unwrap();
+        let tool_a = write_script(&a.join("tool-a"), "echo 'tool-a 1.0.0'");
+        write_script(&a.join("broken"), "exit 1");
+        fs::write(a.join("notes"), "not executable").unwrap();
+        let tool_b = write_script(&b.join("tool-b"), "echo 'tool-b 2.0.0'");
+        std::os::unix::fs::symlink(&tool_a, b.join("alias")).unwrap();
+
+        let paths = |bins: Vec<ExecutableBin>| {
+            let mut paths = bins
+                .iter()
+                .map(|bin| bin.path().to_path_buf())
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+        let bins = scan_locations(&[a.clone(), b.clone(), dir.join("missing")]);
+        assert_eq!(paths(bins), [tool_a, tool_b.clone()]);
+        // Whichever path is found first is the one kept
+        let bins = scan_locations(&[b.clone(), a]);
+        assert_eq!(paths(bins), [b.join("alias"), tool_b]);
+    }
+
+    #[test]
+    fn shadow_resolution_suggests_moving_the_newest_copy_ahead() {
+        let dir = TempDir::new();
+        let [old, other, new] = ["old", "other", "new"].map(|name| {
+            let sub = dir.join(name);
+            fs::create_dir(&sub).unwrap();
+            sub
+        });
+        let name = "symlistow-shadowed-tool";
+        write_script(&old.join(name), "echo 'tool 1.2.0'");
+        write_script(&new.join(name), "echo 'tool 1.10.0'");
+
+        with_path_prefix(&[&old, &other, &new], || {
+            let resolution = shadow_resolution(name).unwrap();
+            assert_eq!(resolution.winner, old.join(name));
+            assert_eq!(resolution.shadowed, [new.join(name)]);
+            assert_eq!(resolution.winner_is_newest, Some(false));
+            let path = env::var_os("PATH").unwrap();
+            let rest = env::split_paths(&path).skip(3);
+            let expected = env::join_paths(
+                [new.clone(), old.clone(), other.clone()]
+                    .into_iter()
+                    .chain(rest),
+            );
+            assert_eq!(resolution.suggested_path, Some(expected.unwrap()));
+        });
+        with_path_prefix(&[&new, &old], || {
+            let resolution = shadow_resolution(name).unwrap();
+            assert_eq!(resolution.winner_is_newest, Some(true));
+            assert_eq!(resolution.suggested_path, None);
+        });
+        assert_eq!(shadow_resolution(name), None);
+    }
+}
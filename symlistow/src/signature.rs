@@ -0,0 +1,197 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+
+use crate::{CommandRunner, ExecutableBin, SystemRunner};
+
+/// Which signature check [`ExecutableBin::verify_signature`] performs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// `codesign --verify` against the binary's embedded signature. macOS only.
+    Codesign,
+    /// `gpg --verify` of a detached signature using only the given keyring.
+    Gpg {
+        /// The detached signature, `<binary>.sig` when `None`.
+        signature: Option<PathBuf>,
+        keyring: PathBuf,
+    },
+}
+
+/// Err Type for failed signature verification
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureError {
+    #[error("{policy} signatures can't be checked on {platform}")]
+    Unsupported {
+        policy: &'static str,
+        platform: &'static str,
+    },
+    #[error("Failed to run signature check: {0}")]
+    Run(#[from] io::Error),
+    #[error("Signature check failed with {status}: {stderr}")]
+    Rejected { status: ExitStatus, stderr: String },
+}
+
+impl ExecutableBin {
+    /// Checks the binary's signature as selected by `policy`.
+    pub fn verify_signature(&self, policy: SignaturePolicy) -> Result<(), SignatureError> {
+        self.verify_signature_with(policy, &SystemRunner)
+    }
+
+    /// Like [`ExecutableBin::verify_signature`], running the check through `runner`.
+    pub fn verify_signature_with(
+        &self,
+        policy: SignaturePolicy,
+        runner: &dyn CommandRunner,
+    ) -> Result<(), SignatureError> {
+        let mut command = match policy {
+            SignaturePolicy::Codesign => {
+                if !cfg!(target_os = "macos") {
+                    return Err(SignatureError::Unsupported {
+                        policy: "codesign",
+                        platform: std::env::consts::OS,
+                    });
+                }
+                let mut command = Command::new("codesign");
+                command.arg("--verify").arg(self.path());
+                command
+            }
+            SignaturePolicy::Gpg { signature, keyring } => {
+                let signature = signature.unwrap_or_else(|| {
+                    let mut sig = OsString::from(self.path());
+                    sig.push(".sig");
+                    sig.into()
+                });
+                let mut command = Command::new("gpg");
+                command
+                    .args(["--batch", "--no-default-keyring", "--keyring"])
+                    .arg(keyring)
+                    .arg("--verify")
+                    .arg(signature)
+                    .arg(self.path());
+                command
+            }
+        };
+        let output = runner.output(&mut command)?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(SignatureError::Rejected {
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Output;
+
+    use super::*;
+
+    /// Records each command's argv and answers with a fixed exit code and stderr.
+    struct MockRunner {
+        calls: RefCell<Vec<Vec<OsString>>>,
+        exit_code: i32,
+        stderr: &'static str,
+    }
+
+    impl MockRunner {
+        fn new(exit_code: i32, stderr: &'static str) -> Self {
+            Self {
+                calls: RefCell::default(),
+                exit_code,
+                stderr,
+            }
+        }
+    }
+
+    impl CommandRunner for MockRunner {
+        fn output(&self, command: &mut Command) -> io::Result<Output> {
+            let argv = std::iter::once(command.get_program())
+                .chain(command.get_args())
+                .map(OsString::from)
+                .collect();
+            self.calls.borrow_mut().push(argv);
+            Ok(Output {
+                status: ExitStatus::from_raw(self.exit_code << 8),
+                stdout: Vec::new(),
+                stderr: self.stderr.into(),
+            })
+        }
+    }
+
+    fn bin() -> ExecutableBin {
+        ExecutableBin::from_parts("/opt/tool".into(), "tool 1.0.0".into(), None)
+    }
+
+    fn gpg(signature: Option<&str>) -> SignaturePolicy {
+        SignaturePolicy::Gpg {
+            signature: signature.map(PathBuf::from),
+            keyring: "/keys/release.gpg".into(),
+        }
+    }
+
+    #[test]
+    fn gpg_verifies_the_detached_signature_against_the_keyring() {
+        let runner = MockRunner::new(0, "");
+        bin().verify_signature_with(gpg(None), &runner).unwrap();
+        bin()
+            .verify_signature_with(gpg(Some("/sigs/tool.asc")), &runner)
+            .unwrap();
+
+        let expected = |signature: &str| {
+            [
+                "gpg",
+                "--batch",
+                "--no-default-keyring",
+                "--keyring",
+                "/keys/release.gpg",
+                "--verify",
+                signature,
+                "/opt/tool",
+            ]
+            .map(OsString::from)
+            .to_vec()
+        };
+        assert_eq!(
+            *runner.calls.borrow(),
+            [expected("/opt/tool.sig"), expected("/sigs/tool.asc")]
+        );
+    }
+
+    #[test]
+    fn a_failed_check_is_rejected_with_its_stderr() {
+        let runner = MockRunner::new(1, "gpg: BAD signature from \"Release Key\"\n");
+        match bin().verify_signature_with(gpg(None), &runner) {
+            Err(SignatureError::Rejected { status, stderr }) => {
+                assert_eq!(status.code(), Some(1));
+                assert_eq!(stderr, r#"gpg: BAD signature from "Release Key""#);
+            }
+            other => panic!("expected Rejected, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn codesign_only_runs_on_macos() {
+        let runner = MockRunner::new(0, "");
+        let result = bin().verify_signature_with(SignaturePolicy::Codesign, &runner);
+        if cfg!(target_os = "macos") {
+            result.unwrap();
+            let argv = ["codesign", "--verify", "/opt/tool"].map(OsString::from);
+            assert_eq!(*runner.calls.borrow(), [argv.to_vec()]);
+        } else {
+            assert!(matches!(
+                result,
+                Err(SignatureError::Unsupported {
+                    policy: "codesign",
+                    ..
+                })
+            ));
+            assert!(runner.calls.borrow().is_empty());
+        }
+    }
+}
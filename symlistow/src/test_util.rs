@@ -0,0 +1,204 @@
+#[cfg(any(feature = "metrics", feature = "tracing"))]
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+#[cfg(feature = "tracing")]
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+#[cfg(any(feature = "metrics", feature = "tracing"))]
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(feature = "metrics")]
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, SharedString, Unit};
+#[cfg(feature = "tracing")]
+use tracing::field::{Field, Visit};
+#[cfg(feature = "tracing")]
+use tracing::span::{Attributes, Id, Record};
+#[cfg(feature = "tracing")]
+use tracing::{Event, Metadata};
+
+/// A fresh directory under the system temp dir, removed with its contents on drop.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let dir = env::temp_dir().join(format!("symlistow-test-{}-{n}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create temp dir");
+        // Resolved so paths compare equal to canonicalized ones
+        Self(fs::canonicalize(dir).expect("resolve temp dir"))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub(crate) fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes an executable `/bin/sh` script running `body` to `path`.
+pub(crate) fn write_script(path: &Path, body: &str) -> PathBuf {
+    fs::write(path, format!("#!/bin/sh\n{body}\n")).expect("write script");
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).expect("chmod script");
+    path.to_path_buf()
+}
+
+/// Serializes tests that change process-wide state such as `$PATH`.
+pub(crate) fn env_lock() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Runs `f` with `dirs` put in front of `$PATH`, restoring it afterwards.
+///
+/// The rest of `$PATH` is kept, so other tests' scripts still find their tools.
+pub(crate) fn with_path_prefix<R>(dirs: &[&Path], f: impl FnOnce() -> R) -> R {
+    struct Restore(OsString);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            // SAFETY: as in `with_path_prefix`
+            unsafe { env::set_var("PATH", &self.0) };
+        }
+    }
+
+    let _lock = env_lock();
+    let original = env::var_os("PATH").unwrap_or_default();
+    let path = env::join_paths(
+        dirs.iter()
+            .map(|dir| dir.to_path_buf())
+            .chain(env::split_paths(&original)),
+    )
+    .expect("join PATH");
+    let _restore = Restore(original);
+    // SAFETY: tests changing the environment hold `env_lock`, and the standard
+    // library takes its own lock when reading it
+    unsafe { env::set_var("PATH", path) };
+    f()
+}
+
+/// A span seen by a [`SpanRecorder`], with every field it was given.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RecordedSpan {
+    pub(crate) name: &'static str,
+    pub(crate) fields: BTreeMap<&'static str, String>,
+}
+
+/// A `tracing` subscriber that remembers the spans created while it is the default.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SpanRecorder(Arc<Mutex<Vec<RecordedSpan>>>);
+
+#[cfg(feature = "tracing")]
+impl SpanRecorder {
+    pub(crate) fn spans(&self) -> Vec<RecordedSpan> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(feature = "tracing")]
+struct FieldWriter<'a>(&'a mut BTreeMap<&'static str, String>);
+
+#[cfg(feature = "tracing")]
+impl Visit for FieldWriter<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name(), format!("{value:?}"));
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let mut span = RecordedSpan {
+            name: attributes.metadata().name(),
+            fields: BTreeMap::new(),
+        };
+        attributes.record(&mut FieldWriter(&mut span.fields));
+        let mut spans = self.0.lock().unwrap();
+        spans.push(span);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.0.lock().unwrap();
+        let span = &mut spans[span.into_u64() as usize - 1];
+        values.record(&mut FieldWriter(&mut span.fields));
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+/// A `metrics` recorder that keeps a running total for every counter, keyed by
+/// name and labels, e.g. `requests{result=ok}`. Gauges and histograms are dropped.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default)]
+pub(crate) struct CounterRecorder(Mutex<BTreeMap<String, Arc<AtomicU64>>>);
+
+#[cfg(feature = "metrics")]
+impl CounterRecorder {
+    pub(crate) fn count(&self, key: &str) -> u64 {
+        let counters = self.0.lock().unwrap();
+        counters
+            .get(key)
+            .map_or(0, |count| count.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl metrics::Recorder for CounterRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &metrics::Metadata<'_>) -> Counter {
+        let labels = key
+            .labels()
+            .map(|label| format!("{}={}", label.key(), label.value()))
+            .collect::<Vec<_>>();
+        let name = format!("{}{{{}}}", key.name(), labels.join(","));
+        let count = self.0.lock().unwrap().entry(name).or_default().clone();
+        Counter::from_arc(count)
+    }
+
+    fn register_gauge(&self, _key: &Key, _metadata: &metrics::Metadata<'_>) -> Gauge {
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, _key: &Key, _metadata: &metrics::Metadata<'_>) -> Histogram {
+        Histogram::noop()
+    }
+}
@@ -0,0 +1,307 @@
+use semver::Version;
+
+/// Err Type for a version report that doesn't contain a semver version
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VersionParseError {
+    #[error("No semver version found in: {0}")]
+    NoVersionFound(String),
+}
+
+/// Err Type for a version report that isn't the exact expected output
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "Version report differs at line {line}: expected {:?}, got {:?}",
+    nth_line(.expected, *.line),
+    nth_line(.actual, *.line)
+)]
+pub struct VersionMismatch {
+    pub expected: String,
+    pub actual: String,
+    /// The first line, counting from 1, where the two differ.
+    pub line: usize,
+}
+
+impl VersionMismatch {
+    /// Compares `actual` with `expected`, locating the first differing line.
+    pub(crate) fn check(expected: &str, actual: &str) -> Result<(), Self> {
+        if expected == actual {
+            return Ok(());
+        }
+        // Splitting on every newline keeps texts that differ only by a trailing one apart
+        let (mut expected_lines, mut actual_lines) = (expected.split('\n'), actual.split('\n'));
+        let mut line = 1;
+        while expected_lines.next() == actual_lines.next() {
+            line += 1;
+        }
+        Err(Self {
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+            line,
+        })
+    }
+}
+
+/// Line `n`, counting from 1, of `text`; empty past its end.
+fn nth_line(text: &str, n: usize) -> &str {
+    text.split('\n').nth(n - 1).unwrap_or("")
+}
+
+/// Extracts the first semver version from a `--version` report.
+///
+/// Reports are split into words and any leading non-digit prefix is dropped, so
+/// `git version 2.43.0`, `v1.2.3` and `go1.21.0` all parse.
+pub fn parse_version_report(report: &str) -> Result<Version, VersionParseError> {
+    report
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')' | '[' | ']'))
+        .map(|word| word.trim_start_matches(|c: char| !c.is_ascii_digit()))
+        .find_map(|word| Version::parse(word).ok())
+        .ok_or_else(|| VersionParseError::NoVersionFound(report.to_string()))
+}
+
+/// Extracts the first run of dot-separated integers from `report`.
+///
+/// `git version 2.43.0` gives `[2, 43, 0]` and `go1.21` gives `[1, 21]`; a report
+/// without any digits gives an empty vector.
+pub(crate) fn numeric_components(report: &str) -> Vec<u64> {
+    let Some(start) = report.find(|c: char| c.is_ascii_digit()) else {
+        return Vec::new();
+    };
+    let mut numbers = Vec::new();
+    for part in report[start..].split('.') {
+        let digits = part
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(part, |end| &part[..end]);
+        match digits.parse() {
+            Ok(n) => numbers.push(n),
+            Err(_) => break,
+        }
+        if digits.len() != part.len() {
+            break;
+        }
+    }
+    numbers
+}
+
+/// The first plausible git commit hash in `report`.
+///
+/// A hash is 7 to 40 hex digits. One right after a `+g` or `-g` (as in
+/// `1.2.3+gabc1234` or `v1.2-3-gabc1234-dirty`) or following the word `commit`
+/// is always taken; a bare word is only taken if it mixes letters and digits, so
+/// plain numbers and words like `added` don't count.
+pub(crate) fn find_commit_hash(report: &str) -> Option<String> {
+    let is_hash = |s: &str| (7..=40).contains(&s.len()) && s.chars().all(|c| c.is_ascii_hexdigit());
+    let mut after_commit = false;
+    for word in report
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')' | '[' | ']'))
+        .filter(|word| !word.is_empty())
+    {
+        let word = word.trim_end_matches([':', '.']);
+        // A marked hash may be followed by more, as in `-g0a1b2c3-dirty`
+        let marked = word
+            .split_once("+g")
+            .or_else(|| word.split_once("-g"))
+            .map(|(_, rest)| {
+                let end = rest.find(|c: char| !c.is_ascii_alphanumeric());
+                end.map_or(rest, |end| &rest[..end])
+            });
+        let bare = word.chars().any(|c| c.is_ascii_digit())
+            && word.chars().any(|c| c.is_ascii_alphabetic());
+        let candidate = match marked {
+            Some(hash) => Some(hash),
+            None if after_commit || bare => Some(word),
+            None => None,
+        };
+        if let Some(hash) = candidate.filter(|hash| is_hash(hash)) {
+            return Some(hash.to_ascii_lowercase());
+        }
+        after_commit = word.eq_ignore_ascii_case("commit");
+    }
+    None
+}
+
+/// Version words that mark an un-stamped build, see
+/// [`crate::ExecutableBin::is_placeholder_version`].
+pub const DEFAULT_PLACEHOLDER_PATTERNS: &[&str] = &["0.0.0", "dev", "unknown", "head"];
+
+/// Whether `report` is empty or has a version word equal (ignoring case) to one
+/// of `patterns`.
+///
+/// Only the first line is searched, as that is where tools print their version;
+/// later lines such as `commit-hash: unknown` don't count. It is split into words
+/// on whitespace, punctuation and brackets, and a `v` before a digit is ignored,
+/// so `v0.0.0` matches `0.0.0`. A word starting with a digit is a version whose
+/// `-` and `+` separated parts match too, so `0.0.0-dev` matches `dev`, but other
+/// words only match whole: `x86_64-unknown-linux-gnu` doesn't match `unknown`.
+pub(crate) fn is_placeholder(report: &str, patterns: &[&str]) -> bool {
+    if report.trim().is_empty() {
+        return true;
+    }
+    let first_line = report.trim_start().lines().next().unwrap_or_default();
+    first_line
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')' | '[' | ']'))
+        .filter(|word| !word.is_empty())
+        .any(|word| {
+            let bare = word
+                .strip_prefix(['v', 'V'])
+                .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+                .unwrap_or(word);
+            let parts = bare
+                .starts_with(|c: char| c.is_ascii_digit())
+                .then(|| bare.split(['-', '+']))
+                .into_iter()
+                .flatten();
+            [word, bare]
+                .into_iter()
+                .chain(parts)
+                .any(|part| patterns.iter().any(|p| part.eq_ignore_ascii_case(p)))
+        })
+}
+
+/// `s` without ANSI escape sequences: CSI sequences such as colors, OSC sequences
+/// such as hyperlinks and terminal titles, and plain escapes such as charset
+/// selection.
+pub(crate) fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameter and intermediate bytes up to a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // nF escapes such as charset selection: intermediates, then a final byte
+            Some(' '..='/') => {
+                for c in chars.by_ref() {
+                    if ('0'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_components_takes_any_number_of_components() {
+        assert_eq!(numeric_components("go version go1.21 linux/amd64"), [1, 21]);
+        assert_eq!(numeric_components("git version 2.43.0"), [2, 43, 0]);
+        assert_eq!(
+            numeric_components("Version 10.0.19041.1 (x64)"),
+            [10, 0, 19041, 1]
+        );
+        assert_eq!(numeric_components("tool 1.2.3-beta.4"), [1, 2, 3]);
+        assert!(numeric_components("tool unknown").is_empty());
+    }
+
+    #[test]
+    fn version_mismatch_locates_the_first_differing_line() {
+        let expected = "tool 1.2.3\nbuilt: 2024-01-01\nfeatures: ssl";
+        assert_eq!(VersionMismatch::check(expected, expected), Ok(()));
+
+        let actual = "tool 1.2.3\nbuilt: 2024-02-01\nfeatures: ssl";
+        let mismatch = VersionMismatch::check(expected, actual).unwrap_err();
+        assert_eq!(mismatch.line, 2);
+        assert_eq!(mismatch.actual, actual);
+        assert_eq!(
+            mismatch.to_string(),
+            r#"Version report differs at line 2: expected "built: 2024-01-01", got "built: 2024-02-01""#
+        );
+
+        // A missing trailing newline is a difference on a line of its own
+        let mismatch = VersionMismatch::check("tool 1.2.3\n", "tool 1.2.3").unwrap_err();
+        assert_eq!(mismatch.line, 2);
+    }
+
+    #[test]
+    fn find_commit_hash_takes_marked_and_mixed_hashes_only() {
+        assert_eq!(
+            find_commit_hash("tool 1.2.3+gabc1234").as_deref(),
+            Some("abc1234")
+        );
+        assert_eq!(
+            find_commit_hash("tool v1.2-3-g0A1B2C3-dirty").as_deref(),
+            Some("0a1b2c3")
+        );
+        assert_eq!(
+            find_commit_hash("tool 1.0.0\ncommit: deadbeefcafe").as_deref(),
+            Some("deadbeefcafe")
+        );
+        assert_eq!(
+            find_commit_hash("rustc 1.75.0 (82e1608df 2023-12-21)").as_deref(),
+            Some("82e1608df")
+        );
+        assert_eq!(find_commit_hash("tool 1.2.3 (1234567)"), None);
+        assert_eq!(find_commit_hash("tool 1.2.3, added feedback"), None);
+        assert_eq!(find_commit_hash("tool 1.2.3"), None);
+    }
+
+    #[test]
+    fn is_placeholder_flags_unstamped_versions() {
+        let placeholder = |report| is_placeholder(report, DEFAULT_PLACEHOLDER_PATTERNS);
+        for report in [
+            "",
+            "  \n",
+            "0.0.0-dev",
+            "tool v0.0.0",
+            "tool 0.0.0+unknown",
+            "tool version unknown",
+            "HEAD",
+            "tool (dev)",
+            "tool 1.4.0-DEV",
+        ] {
+            assert!(placeholder(report), "{report:?} should be a placeholder");
+        }
+        for report in [
+            "git version 2.43.0",
+            "clang version 17.0.6\nTarget: x86_64-unknown-linux-gnu\nThread model: posix",
+            "rustc 1.75.0 (82e1608df 2023-12-21)\nbinary: rustc\ncommit-hash: unknown\n\
+             host: x86_64-unknown-linux-gnu\nrelease: 1.75.0",
+            "devtool 1.0.0",
+            "dev-tools 2.1.0",
+            "head-tracker 3.0.1",
+            "vunknown 1.0.0",
+        ] {
+            assert!(
+                !placeholder(report),
+                "{report:?} should not be a placeholder"
+            );
+        }
+    }
+
+    #[test]
+    fn is_placeholder_uses_the_given_patterns() {
+        assert!(is_placeholder("tool nightly", &["nightly"]));
+        assert!(!is_placeholder(
+            "tool nightly",
+            DEFAULT_PLACEHOLDER_PATTERNS
+        ));
+        assert!(!is_placeholder("tool 0.0.0-dev", &["nightly"]));
+        assert!(is_placeholder("", &[]));
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::ExecutableBin;
+use crate::search::is_executable;
+
+/// How long a file must go without further events before [`watch_and_verify`]
+/// re-verifies it.
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Watches `dir` and re-verifies each executable created or modified in it.
+///
+/// Bursts of events for one file, such as a build writing it in chunks, are
+/// coalesced: a file is verified once it has been quiet for [`WATCH_DEBOUNCE`].
+/// `on_change` receives every binary that verifies; files that aren't executable
+/// or fail verification are skipped.
+///
+/// Blocks until watching fails, and only returns that error.
+pub fn watch_and_verify(dir: &Path, mut on_change: impl FnMut(ExecutableBin)) -> io::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(io::Error::other)?;
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        let wait = pending
+            .values()
+            .map(|last| (*last + WATCH_DEBOUNCE).saturating_duration_since(Instant::now()))
+            .min()
+            .unwrap_or(Duration::MAX);
+        match rx.recv_timeout(wait) {
+            Ok(event) => {
+                let event = event.map_err(io::Error::other)?;
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        pending.insert(path, Instant::now());
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "file watcher stopped",
+                ));
+            }
+        }
+
+        let settled = pending
+            .iter()
+            .filter(|(_, last)| last.elapsed() >= WATCH_DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        for path in settled {
+            pending.remove(&path);
+            if !is_executable(&path) {
+                continue;
+            }
+            if let Ok(bin) = ExecutableBin::new(path) {
+                on_change(bin);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::thread;
+
+    use super::*;
+    use crate::test_util::{TempDir, write_script};
+
+    #[test]
+    fn a_burst_of_writes_is_verified_once() {
+        let dir = TempDir::new();
+        let (tx, rx) = mpsc::channel();
+        let watched = dir.path().to_path_buf();
+        // The watcher never returns, so the thread is left running
+        thread::spawn(move || watch_and_verify(&watched, |bin| tx.send(bin).unwrap()));
+        thread::sleep(Duration::from_millis(300));
+
+        let tool = write_script(&dir.join("tool"), "echo 'tool 1.0.0'");
+        for n in 0..3 {
+            thread::sleep(Duration::from_millis(50));
+            let mut file = OpenOptions::new().append(true).open(&tool).unwrap();
+            writeln!(file, "# chunk {n}").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "not executable").unwrap();
+
+        let bin = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(bin.path(), tool);
+        assert_eq!(bin.version_report(), "tool 1.0.0");
+        assert!(rx.recv_timeout(WATCH_DEBOUNCE * 4).is_err());
+    }
+}